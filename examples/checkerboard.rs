@@ -0,0 +1,43 @@
+//renders a floor receding towards the horizon, with perspective-correct interpolation
+//the squares stay straight-edged instead of bending along the triangle diagonal
+use simpleOpenGL::dimensional::Vector;
+use simpleOpenGL::texture::Texture;
+use simpleOpenGL::obj::Scene;
+use simpleOpenGL::obj::Object;
+use simpleOpenGL::colors::Colors;
+use std::sync::Arc;
+
+const FILE_OUTPUT_PATH:&str="checkerboard.tga";
+const FLOOR_OBJ_PATH:&str="objs/floor.obj";
+const SIZE:usize=500;
+const TEXTURE_SIZE:usize=512;
+const SQUARES:usize=8;
+
+fn checkerboard()->Arc<Texture>{
+    let square=TEXTURE_SIZE/SQUARES;
+    let mut arr=Vec::with_capacity(TEXTURE_SIZE*TEXTURE_SIZE*3);
+    for y in 0..TEXTURE_SIZE{
+        for x in 0..TEXTURE_SIZE{
+            let val=if (x/square+y/square).is_multiple_of(2){255}else{30};
+            arr.extend_from_slice(&[val,val,val]);
+        }
+    }
+    Arc::new(Texture::new(TEXTURE_SIZE,TEXTURE_SIZE,arr,Colors::RGB).expect("checkerboard size"))
+}
+
+fn main()->Result<(),String>{
+    let mut scene=Scene::new(SIZE,SIZE,Vector::new(0.,1.,0.));
+    let camera=scene.camera_mut();
+    camera.position=Vector::new(0.,-0.2,1.6);
+    camera.target=Vector::new(0.,-0.5,-1.);
+    camera.fov=60.;
+
+    let floor=Object::new(Vector::new(0.,0.,0.))
+        .set_text_map(checkerboard())
+        .build(FLOOR_OBJ_PATH)
+        .map_err(|e| format!("{}: {}",FLOOR_OBJ_PATH,e))?;
+    scene.add_obj(floor);
+
+    scene.draw();
+    scene.image().write_tga_file(FILE_OUTPUT_PATH).map_err(|e| format!("{}: {}",FILE_OUTPUT_PATH,e))
+}
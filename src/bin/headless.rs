@@ -0,0 +1,255 @@
+use simpleOpenGL::dimensional::Vector;
+use simpleOpenGL::file_input::read_texture_file;
+use simpleOpenGL::obj::Scene;
+use simpleOpenGL::obj::Object;
+use simpleOpenGL::colors::Colors;
+use simpleOpenGL::shader::Shading;
+use simpleOpenGL::light::Light;
+use simpleOpenGL::shadow::ShadowSettings;
+use simpleOpenGL::ssao;
+use simpleOpenGL::ssao::SsaoSettings;
+use simpleOpenGL::output::ImageFormat;
+use std::env;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
+use std::process;
+
+const FILE_OUTPUT_PATH:&str="image.tga";
+const SIZE:usize=500;
+
+const EXIT_USAGE:i32=2;
+const EXIT_FAILURE:i32=1;
+
+const USAGE:&str="usage: headless --obj <path> [--diffuse <tga>] [--normal <tga>] [--specular <tga>]
+                [--shading <phong|gouraud>] [--size <width>x<height>] [--eye <x,y,z>] [--target <x,y,z>]
+                [--fov <degrees>] [--light <x,y,z>] [--point-light <x,y,z>]... [--shadows] [--ssao]
+                [--ao-output <image>] [--threads <n>] [--stats] [--format <format>] [--output <image|->]
+the output format follows the extension: tga, png or ppm, --format tga|png|ppm|ppm-ascii
+overrides it; --output - writes to stdout and needs --format";
+
+//--output value that streams the image to stdout
+const STDOUT:&str="-";
+
+struct Args{
+    obj:String,
+    diffuse:Option<String>,
+    normal:Option<String>,
+    specular:Option<String>,
+    shading:Shading,
+    width:usize,
+    height:usize,
+    eye:Option<Vector<f32>>,
+    target:Option<Vector<f32>>,
+    fov:Option<f32>,
+    light:Vector<f32>,
+    point_lights:Vec<Vector<f32>>,
+    shadows:bool,
+    ssao:bool,
+    ao_output:Option<String>,
+    threads:Option<usize>,
+    stats:bool,
+    output:String,
+    format:ImageFormat,
+}
+
+fn parse_vector(value:&str)->Result<Vector<f32>,String>{
+    let coords=value.split(',')
+        .map(|coord| coord.trim().parse::<f32>())
+        .collect::<Result<Vec<f32>,_>>()
+        .map_err(|e| format!("invalid vector '{}': {}",value,e))?;
+    if coords.len()!=3{
+        return Err(format!("invalid vector '{}': expected x,y,z",value))
+    }
+    Ok(Vector::new(coords[0],coords[1],coords[2]))
+}
+
+fn parse_size(value:&str)->Result<(usize,usize),String>{
+    let invalid=|| format!("invalid size '{}': expected <width>x<height>",value);
+    let mut parts=value.split('x');
+    let width=parts.next().and_then(|w| w.parse::<usize>().ok()).ok_or_else(invalid)?;
+    let height=parts.next().and_then(|h| h.parse::<usize>().ok()).ok_or_else(invalid)?;
+    if parts.next().is_some() || width==0 || height==0 { return Err(invalid()) }
+    Ok((width,height))
+}
+
+fn parse_args(mut args:impl Iterator<Item=String>)->Result<Args,String>{
+    let (mut obj,mut diffuse,mut normal,mut specular)=(None,None,None,None);
+    let mut shading=Shading::Phong;
+    let (mut width,mut height)=(SIZE,SIZE);
+    let (mut eye,mut target,mut fov)=(None,None,None);
+    let mut light=Vector::new(1.0,1.0,-1.0);
+    let mut point_lights=Vec::new();
+    let mut shadows=false;
+    let (mut ssao,mut ao_output)=(false,None);
+    let (mut threads,mut stats)=(None,false);
+    let mut output=String::from(FILE_OUTPUT_PATH);
+    let mut format=None;
+
+    while let Some(flag)=args.next(){
+        let mut value=|| args.next().ok_or_else(|| format!("missing value for {}",flag));
+        match flag.as_str() {
+            "--obj"=>obj=Some(value()?),
+            "--diffuse"=>diffuse=Some(value()?),
+            "--normal"=>normal=Some(value()?),
+            "--specular"=>specular=Some(value()?),
+            "--shading"=>shading=match value()?.as_str() {
+                "phong"=>Shading::Phong,
+                "gouraud"=>Shading::Gouraud,
+                other=>return Err(format!("unknown shading '{}'",other)),
+            },
+            "--size"=>{
+                let size=parse_size(&value()?)?;
+                width=size.0;
+                height=size.1;
+            },
+            "--eye"=>eye=Some(parse_vector(&value()?)?),
+            "--target"=>target=Some(parse_vector(&value()?)?),
+            "--fov"=>{
+                let value=value()?;
+                fov=match value.parse::<f32>() {
+                    Ok(degrees) if degrees>0. && degrees<180.=>Some(degrees),
+                    _=>return Err(format!("invalid fov '{}': expected degrees between 0 and 180",value)),
+                };
+            },
+            "--light"=>light=parse_vector(&value()?)?,
+            "--point-light"=>point_lights.push(parse_vector(&value()?)?),
+            "--shadows"=>shadows=true,
+            "--ssao"=>ssao=true,
+            "--ao-output"=>ao_output=Some(value()?),
+            "--threads"=>{
+                let value=value()?;
+                threads=match value.parse::<usize>() {
+                    Ok(count) if count>0=>Some(count),
+                    _=>return Err(format!("invalid thread count '{}'",value)),
+                };
+            },
+            "--stats"=>stats=true,
+            "--output"=>output=value()?,
+            "--format"=>{
+                let value=value()?;
+                format=Some(ImageFormat::from_name(&value).ok_or_else(|| format!("unknown image format '{}'",value))?);
+            },
+            _=>return Err(format!("unknown argument '{}'",flag)),
+        }
+    }
+    let obj=obj.ok_or_else(|| String::from("missing --obj"))?;
+    if let Some(path)=&ao_output{
+        if ImageFormat::from_path(path).is_none(){
+            return Err(format!("unknown image format '{}'",path))
+        }
+    }
+    let format=match format.or_else(|| ImageFormat::from_path(&output)) {
+        Some(format)=>format,
+        None if output==STDOUT=>return Err(String::from("--output - needs --format")),
+        None=>return Err(format!("unknown image format '{}'",output)),
+    };
+    Ok(Args{obj,diffuse,normal,specular,shading,width,height,eye,target,fov,light:light.normalize(),point_lights,shadows,ssao,ao_output,threads,stats,output,format})
+}
+
+fn render(args:Args)->Result<(),String>{
+    let mut scene=Scene::new(args.height,args.width,args.light);
+    for position in args.point_lights.iter(){
+        scene.add_light(Light::point(*position));
+    }
+    if args.shadows{
+        scene.set_shadows(Some(ShadowSettings::default()));
+    }
+    if args.ssao{
+        scene.set_ssao(Some(SsaoSettings::default()));
+    }
+    let camera=scene.camera_mut();
+    if let Some(eye)=args.eye{
+        camera.position=eye;
+    }
+    if let Some(target)=args.target{
+        camera.target=target;
+    }
+    if let Some(fov)=args.fov{
+        camera.fov=fov;
+    }
+
+    let mut obj=Object::new(Vector::new(0.,0.,0.)).set_shading(args.shading);
+    if let Some(path)=&args.diffuse{
+        obj=obj.set_text_map(read_texture_file(path,Colors::RGBA).map_err(|e| format!("{}: {}",path,e))?);
+    }
+    if let Some(path)=&args.normal{
+        obj=obj.set_norm_map(read_texture_file(path,Colors::RGBA).map_err(|e| format!("{}: {}",path,e))?);
+    }
+    if let Some(path)=&args.specular{
+        obj=obj.set_sp_map(read_texture_file(path,Colors::Gray).map_err(|e| format!("{}: {}",path,e))?);
+    }
+    let obj=obj.build(&args.obj).map_err(|e| format!("{}: {}",args.obj,e))?;
+    scene.add_obj(obj);
+
+    if let Some(threads)=args.threads{
+        scene.set_threads(threads);
+    }
+    let stats=scene.draw();
+    if args.stats{
+        eprintln!("{}",stats);
+        eprintln!("geometry jobs {:?}",stats.geometry_jobs);
+    }
+    if let Some(path)=&args.ao_output{
+        let (height,width)=(args.height,args.width);
+        let occlusion=ssao::occlusion_image(&scene.ambient_occlusion(&SsaoSettings::default()),height,width);
+        occlusion.write_file(path).map_err(|e| format!("{}: {}",path,e))?;
+    }
+    if args.output==STDOUT{
+        let mut writer=BufWriter::new(io::stdout().lock());
+        return scene.image().write(&mut writer,args.format).and_then(|()| writer.flush())
+            .map_err(|e| format!("stdout: {}",e))
+    }
+    scene.image().write_file_as(&args.output,args.format)
+        .map_err(|e| format!("{}: {}",args.output,e))
+}
+
+fn main(){
+    let args=match parse_args(env::args().skip(1)) {
+        Ok(args)=>args,
+        Err(e)=>{
+            eprintln!("{}\n{}",e,USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    if let Err(e)=render(args){
+        eprintln!("{}",e);
+        process::exit(EXIT_FAILURE);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn parse(args:&[&str])->Result<Args,String>{
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn fov_must_be_between_0_and_180(){
+        for fov in ["0","180","-30","200","nan","wide"].iter(){
+            assert!(parse(&["--obj","head.obj","--fov",fov]).is_err(),"{}",fov);
+        }
+        assert_eq!(parse(&["--obj","head.obj","--fov","60"]).unwrap().fov,Some(60.));
+    }
+
+    //parse errors are what main exits with EXIT_USAGE on
+    #[test]
+    fn stdout_needs_a_format(){
+        assert_eq!(parse(&["--obj","head.obj","--output","-"]).err().unwrap(),"--output - needs --format");
+        let args=parse(&["--obj","head.obj","--output","-","--format","ppm-ascii"]).unwrap();
+        assert_eq!((args.output.as_str(),args.format),("-",ImageFormat::PpmAscii));
+    }
+
+    #[test]
+    fn unknown_formats_are_rejected(){
+        assert!(parse(&["--obj","head.obj","--output","head.jpg"]).is_err());
+        assert!(parse(&["--obj","head.obj","--output","head"]).is_err());
+        assert!(parse(&["--obj","head.obj","--format","gif"]).is_err());
+        assert!(parse(&["--obj","head.obj","--ao-output","ao.bmp"]).is_err());
+        //an explicit format wins over the extension
+        assert_eq!(parse(&["--obj","head.obj","--output","head.jpg","--format","png"]).unwrap().format,ImageFormat::Png);
+        assert_eq!(parse(&["--obj","head.obj","--output","head.PPM"]).unwrap().format,ImageFormat::Ppm);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::dimensional::Vector;
+use crate::matrix::Matrix4;
+
+#[derive(Copy,Clone)]
+pub enum Projection{
+    Perspective,
+    //height of the visible area in world units
+    Orthographic(f32),
+}
+
+#[derive(Clone)]
+pub struct Camera{
+    pub position:Vector<f32>,
+    pub target:Vector<f32>,
+    pub up:Vector<f32>,
+    //vertical field of view in degrees
+    pub fov:f32,
+    pub aspect:f32,
+    pub near:f32,
+    pub far:f32,
+    pub projection:Projection,
+}
+
+impl Camera{
+    pub fn new(position:Vector<f32>,target:Vector<f32>,aspect:f32)->Camera{
+        Camera{position,target,up:Vector::new(0.,1.,0.),fov:45.,aspect,near:0.1,far:100.
+            ,projection:Projection::Perspective}
+    }
+
+    pub fn look_at(&mut self,target:Vector<f32>)->&mut Self{
+        self.target=target;
+        self
+    }
+
+    pub fn view_matrix(&self)->Matrix4{
+        Matrix4::look_at(&self.position,&self.target,&self.up)
+    }
+
+    pub fn projection_matrix(&self)->Matrix4{
+        match self.projection {
+            Projection::Perspective=>Matrix4::perspective(self.fov.to_radians(),self.aspect,self.near,self.far),
+            Projection::Orthographic(height)=>{
+                let (half_height,half_width)=(height/2.,height*self.aspect/2.);
+                Matrix4::orthographic(-half_width,half_width,-half_height,half_height,self.near,self.far)
+            },
+        }
+    }
+
+    //distance along the view direction for a depth in normalized device coordinates
+    pub fn linear_depth(&self,ndc_z:f32)->f32{
+        let (near,far)=(self.near,self.far);
+        match self.projection {
+            Projection::Perspective=>2.*far*near/(far+near-ndc_z*(far-near)),
+            Projection::Orthographic(_)=>(ndc_z*(far-near)+far+near)/2.,
+        }
+    }
+
+    //on screen size in pixels of a world unit at the given distance, height is the image height
+    pub fn pixels_per_unit(&self,depth:f32,height:usize)->f32{
+        match self.projection {
+            Projection::Perspective=>height as f32/2./(self.fov.to_radians()/2.).tan()/depth.max(self.near),
+            Projection::Orthographic(visible)=>height as f32/visible,
+        }
+    }
+}
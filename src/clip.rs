@@ -0,0 +1,117 @@
+use crate::dimensional::Vector;
+use crate::matrix::Vec4;
+use crate::shader::Varyings;
+
+#[derive(Copy,Clone)]
+pub struct ClipVertex{
+    pub position:Vec4,
+    pub varyings:Varyings,
+}
+
+impl ClipVertex{
+    fn lerp(&self,other:&ClipVertex,t:f32)->ClipVertex{
+        let position=self.position+(other.position-self.position)*t;
+        ClipVertex{position,varyings:self.varyings.lerp(&other.varyings,t)}
+    }
+
+    pub fn to_ndc(&self)->Vector<f32>{
+        self.position.project()
+    }
+}
+
+//signed distances to the six frustum planes -w<=x,y,z<=w, inside when >=0
+fn plane_distance(position:&Vec4,plane:usize)->f32{
+    let (axis,sign)=(plane/2,if plane.is_multiple_of(2){1.}else{-1.});
+    position[3]+sign*position[axis]
+}
+
+//true when no vertex is outside any frustum plane, such polygons need no clipping
+pub fn inside(vertices:&[ClipVertex])->bool{
+    vertices.iter().all(|vertex| (0..6).all(|plane| plane_distance(&vertex.position,plane)>=0.))
+}
+
+//Sutherland-Hodgman against every frustum plane, runs before the perspective divide
+//so geometry behind the camera never gets its sign flipped
+pub fn clip_polygon(mut vertices:Vec<ClipVertex>)->Vec<ClipVertex>{
+    for plane in 0..6{
+        if vertices.is_empty(){ break }
+        let mut clipped=Vec::with_capacity(vertices.len()+1);
+        for i in 0..vertices.len(){
+            let (cur,next)=(&vertices[i],&vertices[(i+1)%vertices.len()]);
+            let (d_cur,d_next)=(plane_distance(&cur.position,plane),plane_distance(&next.position,plane));
+            if d_cur>=0.{
+                clipped.push(*cur);
+            }
+            if (d_cur>=0.)!=(d_next>=0.){
+                clipped.push(cur.lerp(next,d_cur/(d_cur-d_next)));
+            }
+        }
+        vertices=clipped;
+    }
+    vertices
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn vertex(x:f32,y:f32,z:f32,w:f32,varying:f32)->ClipVertex{
+        let mut varyings=Varyings::new();
+        varyings.push(varying);
+        ClipVertex{position:Vec4::new(x,y,z,w),varyings}
+    }
+
+    fn assert_vertex(vertex:&ClipVertex,expected:&ClipVertex){
+        let diff=vertex.position-expected.position;
+        assert!([diff.x,diff.y,diff.z,diff.w].iter().all(|val| val.abs()<1e-5)
+                ,"{:?} != {:?}",vertex.position,expected.position);
+        assert!((vertex.varyings.get(0)-expected.varyings.get(0)).abs()<1e-5);
+    }
+
+    #[test]
+    fn crossing_the_near_plane_makes_a_quad(){
+        //the first corner is 2 units in front of the near plane z=-w, the others 1 behind it
+        let triangle=vec![vertex(0.,0.,-3.,1.,10.),vertex(0.5,0.,0.,1.,0.),vertex(0.,0.5,0.,1.,0.)];
+        assert!(!inside(&triangle));
+        let clipped=clip_polygon(triangle.clone());
+        assert_eq!(clipped.len(),4);
+        //cut two thirds of the way along both edges leaving the outside corner
+        assert_vertex(&clipped[0],&vertex(1./3.,0.,-1.,1.,10./3.));
+        assert_vertex(&clipped[1],&triangle[1]);
+        assert_vertex(&clipped[2],&triangle[2]);
+        assert_vertex(&clipped[3],&vertex(0.,1./3.,-1.,1.,10./3.));
+    }
+
+    #[test]
+    fn outside_triangles_vanish(){
+        let triangle=vec![vertex(2.,0.,0.,1.,0.),vertex(3.,0.5,0.,1.,0.),vertex(2.,-0.5,0.5,1.,0.)];
+        assert!(!inside(&triangle));
+        assert!(clip_polygon(triangle).is_empty());
+    }
+
+    #[test]
+    fn inside_triangles_are_unchanged(){
+        let triangle=vec![vertex(-0.5,-0.5,0.,1.,1.),vertex(0.5,-0.5,0.5,1.,2.),vertex(0.,0.5,-0.5,1.,3.)];
+        assert!(inside(&triangle));
+        let clipped=clip_polygon(triangle.clone());
+        assert_eq!(clipped.len(),3);
+        for (vertex,expected) in clipped.iter().zip(triangle.iter()){
+            assert_vertex(vertex,expected);
+        }
+    }
+
+    #[test]
+    fn vertices_on_the_w_zero_boundary(){
+        //the eye itself sits on every plane at once and is kept as it is, without duplicates
+        let triangle=vec![vertex(0.,0.,0.,0.,1.),vertex(0.5,0.,0.,1.,2.),vertex(0.,0.5,0.,1.,3.)];
+        assert!(inside(&triangle));
+        let clipped=clip_polygon(triangle.clone());
+        assert_eq!(clipped.len(),3);
+        assert_vertex(&clipped[0],&triangle[0]);
+        //behind the eye is cut away and nothing left has w<=0
+        let behind=vec![vertex(0.,0.,0.,-1.,1.),vertex(0.5,0.,0.,1.,2.),vertex(0.,0.5,0.,1.,3.)];
+        let clipped=clip_polygon(behind);
+        assert!(!clipped.is_empty());
+        assert!(clipped.iter().all(|vertex| vertex.position.w>0. && inside(&[*vertex])));
+    }
+}
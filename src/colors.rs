@@ -1,8 +1,24 @@
 use crate::dimensional::Vector;
 
+#[derive(Copy,Clone,Debug,PartialEq)]
 pub enum Colors{
     RGBA,
+    RGB,
     Gray,
+    //four f32 channels, for data that does not fit in a byte
+    RGBAFloat,
+}
+
+impl Colors{
+    //size of a single texel
+    pub fn bytes(&self)->usize{
+        match self {
+            Colors::RGBA=>4,
+            Colors::RGB=>3,
+            Colors::Gray=>1,
+            Colors::RGBAFloat=>16,
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -18,6 +34,12 @@ impl TGAColor{
         TGAColor{red,green,blue,alpha}
     }
 
+    //channels in [0,1], clamped
+    pub fn from_rgba(rgba:&[f32;4])->TGAColor{
+        let channel=|val:f32| (val.clamp(0.,1.)*255.).round() as u8;
+        TGAColor::new(channel(rgba[0]),channel(rgba[1]),channel(rgba[2]),channel(rgba[3]))
+    }
+
     pub fn from_arr_to_arr(array:&[TGAColor])->Vec<u8>{
         let mut respond=Vec::new();
         for tga in array.iter(){
@@ -34,11 +56,25 @@ impl TGAColor{
     pub fn add_intensity(&mut self,intensity:f32) {
         if intensity < 0.0 {self.red=0; self.green=0; self.blue=0;}
         else {
-            self.red = (self.red as f32 * intensity) as u8;
-            self.green = (self.green as f32 * intensity) as u8;
-            self.blue = (self.blue as f32 * intensity) as u8;
+            self.red = (self.red as f32 * intensity).min(255.) as u8;
+            self.green = (self.green as f32 * intensity).min(255.) as u8;
+            self.blue = (self.blue as f32 * intensity).min(255.) as u8;
         }
     }
+    pub fn tint(&mut self,color:&Vector<f32>,alpha:f32){
+        self.red=(self.red as f32 * color.x).min(255.) as u8;
+        self.green=(self.green as f32 * color.y).min(255.) as u8;
+        self.blue=(self.blue as f32 * color.z).min(255.) as u8;
+        self.alpha=(self.alpha as f32 * alpha).min(255.) as u8;
+    }
+    pub fn to_rgb(&self)->Vector<f32>{
+        Vector::new(self.red as f32/255.,self.green as f32/255.,self.blue as f32/255.)
+    }
+    //channels are clamped to [0,1] so accumulated light saturates instead of wrapping
+    pub fn with_rgb(&self,rgb:&Vector<f32>)->TGAColor{
+        let channel=|val:f32| (val.clamp(0.,1.)*255.).round() as u8;
+        TGAColor::new(channel(rgb.x),channel(rgb.y),channel(rgb.z),self.alpha)
+    }
     pub fn to_vector(&self) ->Vector<f32>{
         Vector::new(self.red as f32/127.5-1.,self.green as f32/127.5-1.,(self.blue as f32-128.)/127.*(-1.))
     }
@@ -1,89 +1,565 @@
 use std::fs::File;
 use std::io::BufReader;
 use std::io::BufRead;
+use std::path::Path;
 use crate::texture::Texture;
+use crate::tga;
+use crate::tga::Tga;
 use crate::dimensional::Vector;
 use crate::colors::Colors;
 use std::io;
 use std::sync::Arc;
+use std::fmt;
+use std::error;
+use std::str::FromStr;
+use std::collections::HashMap;
+use crate::material::Material;
 
-pub fn read_file(file_path:&str) ->Vec<(Vec<Vector<f32>>, Vec<Vector<f32>>,Vec<Vector<f32>>)> {
-    let res=File::open(file_path).unwrap();
+#[derive(Debug)]
+pub enum ObjError{
+    Io(io::Error),
+    Parse{line:usize,column:usize,token:String},
+    IndexOutOfRange{line:usize,index:isize,len:usize},
+    Unsupported{line:usize,directive:String},
+    UnknownMaterial{line:usize,name:String},
+    Material{path:String,error:Box<ObjError>},
+    Texture{path:String,error:io::Error},
+}
+
+impl fmt::Display for ObjError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(e)=>write!(f,"{}",e),
+            ObjError::Parse{line,column,token}=>
+                write!(f,"line {}, column {}: cannot parse '{}'",line,column,token),
+            ObjError::IndexOutOfRange{line,index,len}=>
+                write!(f,"line {}: index {} is out of range 1..={}",line,index,len),
+            ObjError::Unsupported{line,directive}=>
+                write!(f,"line {}: unsupported directive '{}'",line,directive),
+            ObjError::UnknownMaterial{line,name}=>
+                write!(f,"line {}: material '{}' is not defined in any mtllib",line,name),
+            ObjError::Material{path,error}=>write!(f,"{}: {}",path,error),
+            ObjError::Texture{path,error}=>write!(f,"{}: {}",path,error),
+        }
+    }
+}
+
+impl error::Error for ObjError{}
+
+impl From<io::Error> for ObjError{
+    fn from(e: io::Error) -> Self {
+        ObjError::Io(e)
+    }
+}
+
+//groups, object names and the loose lines and points of blender exports
+const IGNORED_DIRECTIVES:[&str;4]=["g","o","l","p"];
+
+//vertices are distinct v/vt/vn combinations, the triangles index into the three parallel arrays;
+//without vn faces of a smoothing group share the averaged normal of a position within the group,
+//faces outside any group (the default, s off or s 0) stay flat
+pub struct ObjFile{
+    pub positions:Vec<Vector<f32>>,
+    pub text_coords:Vec<Vector<f32>>,
+    pub normals:Vec<Vector<f32>>,
+    pub triangles:Vec<[usize;3]>,
+    //per triangle index into material_names, None before the first usemtl
+    pub materials:Vec<Option<usize>>,
+    pub material_names:Vec<(usize,String)>,
+    pub material_libs:Vec<String>,
+}
+
+//texture paths are kept as written in the file, the caller decides what they are relative to
+pub struct MtlEntry{
+    pub material:Material,
+    pub text_map:Option<String>,
+    pub norm_map:Option<String>,
+    pub sp_map:Option<String>,
+}
+
+//tokens with their 1-based column in the line
+fn tokens(line:&str)->impl Iterator<Item=(usize,&str)>{
+    line.split_whitespace()
+        .map(move |token| (token.as_ptr() as usize-line.as_ptr() as usize+1,token))
+}
+
+fn parse<T:FromStr>(line:usize,column:usize,token:&str)->Result<T,ObjError>{
+    token.parse::<T>().map_err(|_| ObjError::Parse{line,column,token:String::from(token)})
+}
+
+fn lookup(points:&[[f32;3]],line:usize,index:usize)->Result<Vector<f32>,ObjError>{
+    if index==0 || index>points.len(){
+        return Err(ObjError::IndexOutOfRange{line,index:index as isize,len:points.len()})
+    }
+    let point=points[index-1];
+    Ok(Vector::new(point[0], point[1], point[2]))
+}
+
+struct VertexRef{
+    point:usize,
+    text:Option<usize>,
+    norm:Option<usize>,
+}
+
+//negative indices are relative to the elements read so far, -1 is the last one
+fn resolve_index(line:usize,column:usize,token:&str,len:usize)->Result<usize,ObjError>{
+    let index=parse::<isize>(line,column,token)?;
+    let resolved=if index<0{len as isize+index+1}else{index};
+    if resolved<1{
+        return Err(ObjError::IndexOutOfRange{line,index,len})
+    }
+    Ok(resolved as usize)
+}
+
+//accepts v, v/vt, v//vn and v/vt/vn
+fn parse_vertex_ref(line:usize,column:usize,coords:&str,lens:(usize,usize,usize))->Result<VertexRef,ObjError>{
+    let mut parts=Vec::new();
+    let mut offset=0;
+    for part in coords.split('/'){
+        parts.push((column+offset,part));
+        offset+=part.len()+1;
+    }
+    if parts.len()>3 || parts[0].1.is_empty() || (parts.len()==2 && parts[1].1.is_empty())
+        || (parts.len()==3 && parts[2].1.is_empty()){
+        return Err(ObjError::Parse{line,column,token:String::from(coords)})
+    }
+    let point=resolve_index(line,parts[0].0,parts[0].1,lens.0)?;
+    let text=match parts.get(1) {
+        Some((column,token)) if !token.is_empty()=>Some(resolve_index(line,*column,token,lens.1)?),
+        _=>None,
+    };
+    let norm=match parts.get(2) {
+        Some((column,token))=>Some(resolve_index(line,*column,token,lens.2)?),
+        None=>None,
+    };
+    Ok(VertexRef{point,text,norm})
+}
+
+//ear clipping on the polygon projected to its dominant plane, falls back to a fan
+//for whatever is left when no ear can be found (degenerate or self-intersecting input)
+fn triangulate(polygon:&[Vector<f32>])->Vec<[usize;3]>{
+    if polygon.len()==3{ return vec![[0,1,2]] }
+
+    let mut normal=Vector::new(0.,0.,0.);
+    for i in 0..polygon.len(){
+        let (cur,next)=(polygon[i],polygon[(i+1)%polygon.len()]);
+        normal.x+=(cur.y-next.y)*(cur.z+next.z);
+        normal.y+=(cur.z-next.z)*(cur.x+next.x);
+        normal.z+=(cur.x-next.x)*(cur.y+next.y);
+    }
+    let (ax,ay,az)=(normal.x.abs(),normal.y.abs(),normal.z.abs());
+    let (u,v,sign)=if az>=ax && az>=ay{(0,1,normal.z)}
+        else if ax>=ay{(1,2,normal.x)}
+        else{(2,0,normal.y)};
+    let sign=if sign<0.{-1.}else{1.};
+    let projected=polygon.iter().map(|p| (p[u],p[v])).collect::<Vec<(f32,f32)>>();
+    let cross=|a:usize,b:usize,c:usize|{
+        let (a,b,c)=(projected[a],projected[b],projected[c]);
+        ((b.0-a.0)*(c.1-a.1)-(b.1-a.1)*(c.0-a.0))*sign
+    };
+
+    let mut remaining=(0..polygon.len()).collect::<Vec<usize>>();
+    let mut triangles=Vec::new();
+    while remaining.len()>3{
+        let len=remaining.len();
+        let ear=(0..len).find(|&i|{
+            let (prev,cur,next)=(remaining[(i+len-1)%len],remaining[i],remaining[(i+1)%len]);
+            if cross(prev,cur,next)<=f32::EPSILON{ return false }
+            !remaining.iter()
+                .filter(|&&p| p!=prev && p!=cur && p!=next)
+                .any(|&p| cross(prev,cur,p)>=0. && cross(cur,next,p)>=0. && cross(next,prev,p)>=0.)
+        });
+        match ear {
+            Some(i)=>{
+                triangles.push([remaining[(i+len-1)%len],remaining[i],remaining[(i+1)%len]]);
+                remaining.remove(i);
+            },
+            None=>break,
+        }
+    }
+    for i in 1..remaining.len()-1{
+        triangles.push([remaining[0],remaining[i],remaining[i+1]]);
+    }
+    triangles
+}
+
+pub fn read_file(file_path:&str) ->Result<ObjFile,ObjError> {
+    let res=File::open(file_path)?;
     let reader=BufReader::new(res);
     let mut points=Vec::new();
-    let mut triangles=Vec::new();
+    let mut faces=Vec::new();
     let mut texture_points=Vec::new();
     let mut normal_points=Vec::new();
+    let mut material_names:Vec<(usize,String)>=Vec::new();
+    let mut material_libs=Vec::new();
+    let mut material=None;
+    //smoothing group of the next faces, 0 is off like at the start of the file
+    let mut smoothing=0u32;
 
-    for line in reader.lines(){
-        match line {
-            Ok(line)=> {
-                if line.starts_with("v ") || line.starts_with("vt ")|| line.starts_with("vn "){
-                    let subline=&line[2..];
-                    let mut point:[f32;3]=[0.0;3];
-                    for (index,coord) in subline.split_whitespace().enumerate(){
-                        point[index]=coord.parse::<f32>().unwrap();
-                    }
-                    if line.starts_with("v "){
-                        points.push(point);
-                    }else if line.starts_with("vt "){
-                        texture_points.push(point);
-                    }
-                    else{
-                        normal_points.push(point);
-                    }
+    for (line_index,line) in reader.lines().enumerate(){
+        let line=line?;
+        let line_num=line_index+1;
+        let mut tokens=tokens(&line);
+        let directive=match tokens.next() {
+            Some((_,token))=>token,
+            None=>continue,
+        };
+        match directive {
+            "v" | "vt" | "vn"=>{
+                let required=if directive=="vt"{1}else{3};
+                let mut point:[f32;3]=[0.0;3];
+                let mut count=0;
+                for (column,coord) in tokens.take(3){
+                    point[count]=parse::<f32>(line_num,column,coord)?;
+                    count+=1;
+                }
+                if count<required{
+                    return Err(ObjError::Parse{line:line_num,column:line.len()+1,token:String::new()})
                 }
-                if line.starts_with("f "){
-                    let mut triangle:[usize;3]=[0;3];
-                    let mut text_triangle:[usize;3]=[0;3];
-                    let mut norm_triangle:[usize;3]=[0;3];
-
-                    let line=&line[2..];
-                    for (index,coords) in line.split_whitespace().enumerate(){
-                        let first_slash=coords.find('/').unwrap();
-                        let last_slash=coords.rfind('/').unwrap();
-                        let point=&coords[..first_slash];
-                        triangle[index]=point.parse::<usize>().unwrap();
-                        let tr_point=&coords[first_slash+1..last_slash];
-                        text_triangle[index]=tr_point.parse::<usize>().unwrap();
-                        norm_triangle[index]=tr_point.parse::<usize>().unwrap();
-                    }
-                    triangles.push((triangle,text_triangle,norm_triangle));
+                match directive {
+                    "v"=>points.push(point),
+                    "vt"=>texture_points.push(point),
+                    _=>normal_points.push(point),
                 }
             },
-            Err(_e) =>()
+            "f"=>{
+                let mut face=Vec::new();
+                for (column,coords) in tokens{
+                    face.push(parse_vertex_ref(line_num,column,coords,
+                                               (points.len(),texture_points.len(),normal_points.len()))?);
+                }
+                if face.len()<3{
+                    return Err(ObjError::Parse{line:line_num,column:line.len()+1,token:String::new()})
+                }
+                faces.push((line_num,face,material,smoothing));
+            },
+            "s"=>smoothing=match tokens.next() {
+                Some((_,"off")) | None=>0,
+                Some((_,"on"))=>1,
+                Some((column,token))=>parse::<u32>(line_num,column,token)?,
+            },
+            "mtllib"=>material_libs.extend(tokens.map(|(_,token)| String::from(token))),
+            "usemtl"=>{
+                let name=rest_of_line(&line,tokens);
+                material=match material_names.iter().position(|(_,used)| *used==name) {
+                    Some(index)=>Some(index),
+                    None=>{
+                        material_names.push((line_num,name));
+                        Some(material_names.len()-1)
+                    },
+                };
+            },
+            _ if directive.starts_with('#')=>(),
+            _ if IGNORED_DIRECTIVES.contains(&directive)=>(),
+            _=>return Err(ObjError::Unsupported{line:line_num,directive:String::from(directive)}),
+        }
+    }
+    //corners without vn in a smoothing group get the area weighted normal of the group's
+    //triangles around their position, so they can be shared like corners with vn
+    let mut smooth_normals=HashMap::new();
+    let mut polygons=Vec::new();
+    for (line,face,material,smoothing) in faces {
+        let mut polygon=Vec::new();
+        for vertex in face.iter() {
+            polygon.push(lookup(&points,line,vertex.point)?);
         }
+        let mut text_polygon=Vec::new();
+        for vertex in face.iter(){
+            text_polygon.push(match vertex.text {
+                Some(index)=>lookup(&texture_points,line,index)?,
+                None=>Vector::new(0.,0.,0.),
+            });
+        }
+        let mut norm_polygon=Vec::new();
+        for vertex in face.iter(){
+            norm_polygon.push(match vertex.norm {
+                Some(index)=>Some(lookup(&normal_points,line,index)?.normalize()),
+                None=>None,
+            });
+        }
+        let corners=triangulate(&polygon);
+        if smoothing!=0{
+            for &[a,b,c] in corners.iter(){
+                let normal=(polygon[b]-polygon[a]).vector_prod(polygon[c]-polygon[a]);
+                for &corner in [a,b,c].iter().filter(|&&corner| face[corner].norm.is_none()){
+                    let sum=smooth_normals.entry((face[corner].point,smoothing)).or_insert(Vector::new(0.,0.,0.));
+                    *sum=*sum+normal;
+                }
+            }
+        }
+        polygons.push((face,polygon,text_polygon,norm_polygon,corners,material,smoothing));
     }
-    let mut real_coords =Vec::new();
-    for triangle in triangles {
-        let mut real_coord = Vec::new();
-        for val in triangle.0.iter() {
-            let point = *points.get(val - 1).unwrap();
-            real_coord.push(Vector::new(point[0], point[1], point[2]));
+
+    let (mut positions,mut text_coords,mut normals)=(Vec::new(),Vec::new(),Vec::new());
+    let mut shared=HashMap::new();
+    let mut triangles=Vec::new();
+    let mut materials=Vec::new();
+    for (face,polygon,text_polygon,norm_polygon,corners,material,smoothing) in polygons{
+        for [a,b,c] in corners{
+            //corners without vn outside a smoothing group get the flat face normal and are never shared
+            let face_normal=(polygon[b]-polygon[a]).vector_prod(polygon[c]-polygon[a]).normalize();
+            let mut triangle=[0;3];
+            for (index,&corner) in triangle.iter_mut().zip([a,b,c].iter()){
+                let vertex=&face[corner];
+                //vn pins the normal, without it every group has its own
+                let group=if vertex.norm.is_some(){0}else{smoothing};
+                let key=(vertex.point,vertex.text,vertex.norm,group);
+                let shareable=vertex.norm.is_some() || smoothing!=0;
+                *index=match shared.get(&key) {
+                    Some(&shared_index) if shareable=>shared_index,
+                    _=>{
+                        positions.push(polygon[corner]);
+                        text_coords.push(text_polygon[corner]);
+                        normals.push(match norm_polygon[corner] {
+                            Some(normal)=>normal,
+                            None if smoothing!=0=>smooth_normals[&(vertex.point,smoothing)].normalize(),
+                            None=>face_normal,
+                        });
+                        if shareable{
+                            shared.insert(key,positions.len()-1);
+                        }
+                        positions.len()-1
+                    },
+                };
+            }
+            triangles.push(triangle);
+            materials.push(material);
         }
-        let mut real_text_coord=Vec::new();
-        for text_val in triangle.1.iter(){
-            let point = *texture_points.get(text_val - 1).unwrap();
-            real_text_coord.push(Vector::new(point[0], point[1], point[2]))
+    }
+    Ok(ObjFile{positions,text_coords,normals,triangles,materials,material_names,material_libs})
+}
+
+//names and paths may contain spaces, take everything after the directive
+fn rest_of_line<'a>(line:&'a str,mut tokens:impl Iterator<Item=(usize,&'a str)>)->String{
+    match tokens.next() {
+        Some((column,_))=>String::from(line[column-1..].trim()),
+        None=>String::new(),
+    }
+}
+
+//texture directives may carry options before the file name (map_Bump -bm 1.0 file.tga)
+fn map_path<'a>(line_num:usize,line:&str,tokens:impl Iterator<Item=(usize,&'a str)>)->Result<String,ObjError>{
+    match tokens.last() {
+        Some((_,token))=>Ok(String::from(token)),
+        None=>Err(ObjError::Parse{line:line_num,column:line.len()+1,token:String::new()}),
+    }
+}
+
+fn parse_color<'a>(line_num:usize,line:&str,tokens:impl Iterator<Item=(usize,&'a str)>)->Result<Vector<f32>,ObjError>{
+    let mut color=[0.;3];
+    let mut count=0;
+    for (column,token) in tokens.take(3){
+        color[count]=parse::<f32>(line_num,column,token)?;
+        count+=1;
+    }
+    match count {
+        0=>Err(ObjError::Parse{line:line_num,column:line.len()+1,token:String::new()}),
+        //a single value means grey
+        1=>Ok(Vector::new(color[0],color[0],color[0])),
+        3=>Ok(Vector::new(color[0],color[1],color[2])),
+        _=>Err(ObjError::Parse{line:line_num,column:line.len()+1,token:String::new()}),
+    }
+}
+
+pub fn read_mtl_file(file_path:&str)->Result<Vec<MtlEntry>,ObjError>{
+    let res=File::open(file_path)?;
+    let reader=BufReader::new(res);
+    let mut entries:Vec<MtlEntry>=Vec::new();
+
+    for (line_index,line) in reader.lines().enumerate(){
+        let line=line?;
+        let line_num=line_index+1;
+        let mut tokens=tokens(&line);
+        let directive=match tokens.next() {
+            Some((_,token))=>token,
+            None=>continue,
+        };
+        if directive.starts_with('#'){ continue }
+        if directive=="newmtl"{
+            let name=rest_of_line(&line,tokens);
+            entries.push(MtlEntry{material:Material::new(&name),text_map:None,norm_map:None,sp_map:None});
+            continue
         }
-        let mut real_norm_coord=Vec::new();
-        for text_val in triangle.2.iter(){
-            let point = *texture_points.get(text_val - 1).unwrap();
-            real_norm_coord.push(Vector::new(point[0], point[1], point[2]))
+        let entry=match entries.last_mut() {
+            Some(entry)=>entry,
+            None=>return Err(ObjError::Unsupported{line:line_num,directive:String::from(directive)}),
+        };
+        match directive {
+            "Ka"=>entry.material.ambient=parse_color(line_num,&line,tokens)?,
+            "Kd"=>entry.material.diffuse=parse_color(line_num,&line,tokens)?,
+            "Ks"=>entry.material.specular=parse_color(line_num,&line,tokens)?,
+            "Ns"|"d"=>{
+                let (column,token)=tokens.next()
+                    .ok_or_else(|| ObjError::Parse{line:line_num,column:line.len()+1,token:String::new()})?;
+                let value=parse::<f32>(line_num,column,token)?;
+                if directive=="Ns"{ entry.material.shininess=value }else{ entry.material.dissolve=value }
+            },
+            "map_Kd"=>entry.text_map=Some(map_path(line_num,&line,tokens)?),
+            "map_Bump"|"map_bump"|"bump"|"norm"=>entry.norm_map=Some(map_path(line_num,&line,tokens)?),
+            "map_Ks"=>entry.sp_map=Some(map_path(line_num,&line,tokens)?),
+            //everything else we cannot use, from illum to pbr extensions like Pr, Pm or map_Ns
+            _=>(),
         }
-        real_coords.push((real_coord,real_text_coord,real_norm_coord));
     }
-    real_coords
+    Ok(entries)
 }
 
+//the first row of the texture is the top of the image, at v=0.
+//tga files use the native codec, anything else goes through imagefmt
 pub fn read_texture_file(path:&str,color_format:Colors)->Result<Arc<Texture>,io::Error>{
     let texture=File::open(path)?;
     let mut reader=BufReader::new(texture);
-    let texture=match color_format {
-        Colors::RGBA=> imagefmt::read_from(&mut reader,imagefmt::ColFmt::RGBA).unwrap(),
-        Colors::Gray=> imagefmt::read_from(&mut reader,imagefmt::ColFmt::Y).unwrap(),
+    let is_tga=Path::new(path).extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("tga"));
+    let tga=if is_tga{
+        tga::read(reader)?
+    }else{
+        let (fmt,color_type)=match color_format {
+            Colors::RGBA | Colors::RGBAFloat=>(imagefmt::ColFmt::RGBA,Colors::RGBA),
+            Colors::RGB=>(imagefmt::ColFmt::RGB,Colors::RGB),
+            Colors::Gray=>(imagefmt::ColFmt::Y,Colors::Gray),
+        };
+        let image=match imagefmt::read_from(&mut reader,fmt) {
+            Ok(image)=>image,
+            Err(imagefmt::Error::Io(e))=>return Err(e),
+            Err(e)=>return Err(io::Error::new(io::ErrorKind::InvalidData,e.to_string())),
+        };
+        Tga{width:image.w,height:image.h,color_type,data:image.buf}
     };
-    let (height,width)=(texture.h,texture.w);
-    let arr=texture.buf;
-    Ok(Arc::new(Texture::new(height,width,arr,color_format)))
-}
\ No newline at end of file
+    let (height,width)=(tga.height,tga.width);
+    let texture=Texture::from_pixels(height,width,tga.into_pixels(color_format))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData,e.to_string()))?;
+    Ok(Arc::new(texture))
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::fs;
+
+    //writes text to a file of its own in the temp dir and returns the path
+    fn temp_file(name:&str,text:&str)->String{
+        let path=std::env::temp_dir().join(format!("simpleOpenGL-{}-{}",std::process::id(),name));
+        fs::write(&path,text).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    const QUAD:&str="v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn smooth_faces_without_normals_share_vertices(){
+        let obj=read_file(&temp_file("smooth.obj",&format!("{}s 1\nf 1 2 3\nf 1 3 4\n",QUAD))).unwrap();
+        assert_eq!(obj.triangles.len(),2);
+        assert_eq!(obj.positions.len(),4);
+        for normal in &obj.normals{
+            assert!((normal.z-1.).abs()<1e-6);
+        }
+    }
+
+    #[test]
+    fn smoothing_off_keeps_flat_corners(){
+        let obj=read_file(&temp_file("flat.obj",&format!("{}s 1\ns off\nf 1 2 3\nf 1 3 4\n",QUAD))).unwrap();
+        assert_eq!(obj.positions.len(),6);
+    }
+
+    #[test]
+    fn faces_without_s_are_flat(){
+        //two faces folded along the edge 1-2 keep their own normals
+        let obj=read_file(&temp_file("nos.obj","v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nf 1 2 3\nf 2 1 4\n")).unwrap();
+        assert_eq!(obj.positions.len(),6);
+        for &index in obj.triangles[0].iter(){
+            assert!((obj.normals[index].z-1.).abs()<1e-6);
+        }
+        for &index in obj.triangles[1].iter(){
+            assert!((obj.normals[index].y-1.).abs()<1e-6);
+        }
+    }
+
+    #[test]
+    fn smoothing_groups_do_not_share_normals(){
+        let text="v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\ns 1\nf 1 2 3\ns 2\nf 2 1 4\n";
+        let obj=read_file(&temp_file("groups.obj",text)).unwrap();
+        assert_eq!(obj.positions.len(),6);
+        assert!((obj.normals[obj.triangles[0][0]].z-1.).abs()<1e-6);
+        assert!((obj.normals[obj.triangles[1][0]].y-1.).abs()<1e-6);
+    }
+
+    #[test]
+    fn smooth_normals_average_the_faces_around_a_position(){
+        //two faces folded along the edge 1-2
+        let obj=read_file(&temp_file("fold.obj","v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\ns 1\nf 1 2 3\nf 2 1 4\n")).unwrap();
+        assert_eq!(obj.positions.len(),4);
+        let shared=obj.triangles[0][0];
+        let normal=obj.normals[shared];
+        let half=0.5f32.sqrt();
+        assert!((normal.x).abs()<1e-6 && (normal.y-half).abs()<1e-6 && (normal.z-half).abs()<1e-6);
+    }
+
+    //twice the signed area of a triangle in the xy plane
+    fn area(a:&Vector<f32>,b:&Vector<f32>,c:&Vector<f32>)->f32{
+        (b.x-a.x)*(c.y-a.y)-(b.y-a.y)*(c.x-a.x)
+    }
+
+    #[test]
+    fn ear_clipping_covers_concave_polygons(){
+        //an L and an arrow whose notch would break a fan from the first vertex
+        let shapes=[
+            vec![(0.,0.),(2.,0.),(2.,1.),(1.,1.),(1.,2.),(0.,2.)],
+            vec![(0.,0.),(2.,1.),(4.,0.),(2.,3.)],
+            vec![(1.,1.),(0.,2.),(0.,0.),(2.,0.),(2.,2.)],
+        ];
+        for shape in shapes.iter(){
+            let polygon=shape.iter().map(|&(x,y)| Vector::new(x,y,0.)).collect::<Vec<Vector<f32>>>();
+            let triangles=triangulate(&polygon);
+            assert_eq!(triangles.len(),polygon.len()-2);
+            let len=polygon.len();
+            let expected=(0..len).map(|i| area(&Vector::new(0.,0.,0.),&polygon[i],&polygon[(i+1)%len])).sum::<f32>();
+            let mut total=0.;
+            for &[a,b,c] in triangles.iter(){
+                let twice=area(&polygon[a],&polygon[b],&polygon[c]);
+                //every triangle keeps the winding of the polygon, so none folds over
+                assert!(twice>0.);
+                total+=twice;
+            }
+            assert!((total-expected).abs()<1e-5,"{} != {}",total,expected);
+        }
+    }
+
+    #[test]
+    fn concave_faces_are_triangulated_in_any_plane(){
+        //the L in the xz plane
+        let text="v 0 0 0\nv 0 0 2\nv 1 0 2\nv 1 0 1\nv 2 0 1\nv 2 0 0\nf 1 2 3 4 5 6\n";
+        let obj=read_file(&temp_file("plane.obj",text)).unwrap();
+        assert_eq!(obj.triangles.len(),4);
+        let total=obj.triangles.iter().map(|&[a,b,c]|{
+            let (a,b,c)=(obj.positions[a],obj.positions[b],obj.positions[c]);
+            (b-a).vector_prod(c-a).length()/2.
+        }).sum::<f32>();
+        assert!((total-3.).abs()<1e-5);
+    }
+
+    #[test]
+    fn loose_lines_and_points_are_ignored(){
+        let text=format!("{}o Cube\ng side\nf 1 2 3 4\nl 1 2\nl 2 3 4\np 1\n",QUAD);
+        let obj=read_file(&temp_file("loose.obj",&text)).unwrap();
+        assert_eq!(obj.triangles.len(),2);
+        let error=read_file(&temp_file("curve.obj",&format!("{}curv 0 1 1 2\n",QUAD))).err().unwrap();
+        assert!(matches!(error,ObjError::Unsupported{line:5,..}));
+    }
+
+    #[test]
+    fn unknown_mtl_statements_are_skipped(){
+        let text="newmtl skin\nKd 0.5 0.5 0.5\nPr 0.4\nPm 0.1\nmap_Ns rough.tga\nillum 2\nNs 20\n";
+        let entries=read_mtl_file(&temp_file("pbr.mtl",text)).unwrap();
+        assert_eq!(entries.len(),1);
+        assert_eq!(entries[0].material.shininess,20.);
+        assert_eq!(entries[0].material.diffuse.x,0.5);
+    }
+
+    #[test]
+    fn corners_with_normals_are_shared(){
+        let text=format!("{}vn 0 0 1\nf 1//1 2//1 3//1\nf 1//1 3//1 4//1\n",QUAD);
+        let obj=read_file(&temp_file("normals.obj",&text)).unwrap();
+        assert_eq!(obj.positions.len(),4);
+        assert_eq!(obj.triangles,vec![[0,1,2],[0,2,3]]);
+    }
+}
@@ -4,4 +4,18 @@ pub mod texture;
 pub mod file_input;
 pub mod matrix;
 pub mod obj;
-pub mod colors;
\ No newline at end of file
+pub mod colors;
+pub mod material;
+pub mod camera;
+
+pub mod clip;
+pub mod mesh;
+pub mod raster;
+pub mod shader;
+pub mod light;
+pub mod shadow;
+pub mod ssao;
+pub mod tga;
+pub mod output;
+pub mod pool;
+pub mod stats;
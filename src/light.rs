@@ -0,0 +1,98 @@
+use crate::dimensional::Vector;
+
+//intensity is divided by constant + linear*d + quadratic*d^2
+#[derive(Copy,Clone,Debug)]
+pub struct Attenuation{
+    pub constant:f32,
+    pub linear:f32,
+    pub quadratic:f32,
+}
+
+impl Attenuation{
+    pub fn new(constant:f32,linear:f32,quadratic:f32)->Attenuation{
+        Attenuation{constant,linear,quadratic}
+    }
+    fn factor(&self,distance:f32)->f32{
+        1./(self.constant+self.linear*distance+self.quadratic*distance*distance).max(f32::EPSILON)
+    }
+}
+
+//positions and directions are in world space, directions point the way the light travels
+#[derive(Copy,Clone,Debug)]
+pub enum Light{
+    Directional{direction:Vector<f32>,color:Vector<f32>,intensity:f32,shadow:bool},
+    Point{position:Vector<f32>,attenuation:Attenuation,color:Vector<f32>,intensity:f32,shadow:bool},
+    //cone angles are half-angles in degrees, full light inside inner fading to none at outer
+    Spot{position:Vector<f32>,direction:Vector<f32>,inner:f32,outer:f32,attenuation:Attenuation,
+        color:Vector<f32>,intensity:f32,shadow:bool},
+}
+
+impl Light{
+    pub fn directional(direction:Vector<f32>)->Light{
+        Light::Directional{direction:direction.normalize(),color:Vector::new(1.,1.,1.),intensity:1.,shadow:true}
+    }
+    pub fn point(position:Vector<f32>)->Light{
+        Light::Point{position,attenuation:Attenuation::new(1.,0.,0.),color:Vector::new(1.,1.,1.),intensity:1.,shadow:true}
+    }
+    pub fn spot(position:Vector<f32>,direction:Vector<f32>,inner:f32,outer:f32)->Light{
+        Light::Spot{position,direction:direction.normalize(),inner,outer,attenuation:Attenuation::new(1.,0.,0.)
+            ,color:Vector::new(1.,1.,1.),intensity:1.,shadow:true}
+    }
+
+    pub fn set_color(mut self,new_color:Vector<f32>)->Self{
+        match &mut self {
+            Light::Directional{color,..} | Light::Point{color,..} | Light::Spot{color,..}=>*color=new_color,
+        }
+        self
+    }
+    pub fn set_intensity(mut self,new_intensity:f32)->Self{
+        match &mut self {
+            Light::Directional{intensity,..} | Light::Point{intensity,..} | Light::Spot{intensity,..}=>
+                *intensity=new_intensity,
+        }
+        self
+    }
+    //only used when the scene renders shadows
+    pub fn set_shadow(mut self,new_shadow:bool)->Self{
+        match &mut self {
+            Light::Directional{shadow,..} | Light::Point{shadow,..} | Light::Spot{shadow,..}=>*shadow=new_shadow,
+        }
+        self
+    }
+    pub fn casts_shadow(&self)->bool{
+        match self {
+            Light::Directional{shadow,..} | Light::Point{shadow,..} | Light::Spot{shadow,..}=>*shadow,
+        }
+    }
+    //ignored by directional lights
+    pub fn set_attenuation(mut self,new_attenuation:Attenuation)->Self{
+        match &mut self {
+            Light::Point{attenuation,..} | Light::Spot{attenuation,..}=>*attenuation=new_attenuation,
+            Light::Directional{..}=>(),
+        }
+        self
+    }
+
+    //unit vector from the point towards the light and the radiance arriving at the point
+    pub fn illuminate(&self,point:&Vector<f32>)->(Vector<f32>,Vector<f32>){
+        match self {
+            Light::Directional{direction,color,intensity,..}=>(*direction*-1.,*color*(*intensity)),
+            Light::Point{position,attenuation,color,intensity,..}=>{
+                let to_light=*position-*point;
+                let factor=attenuation.factor(to_light.length());
+                (to_light.normalize(),*color*(intensity*factor))
+            },
+            Light::Spot{position,direction,inner,outer,attenuation,color,intensity,..}=>{
+                let to_light=*position-*point;
+                let dir=to_light.normalize();
+                let cos=(dir*-1.).scalar_prod(direction);
+                let (cos_inner,cos_outer)=(inner.to_radians().cos(),outer.to_radians().cos());
+                let cone=if cos>=cos_inner{1.}
+                    else if cos<=cos_outer{0.}
+                    else{(cos-cos_outer)/(cos_inner-cos_outer).max(f32::EPSILON)};
+                let factor=attenuation.factor(to_light.length())*cone;
+                (dir,*color*(intensity*factor))
+            },
+        }
+    }
+}
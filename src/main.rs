@@ -6,8 +6,6 @@ use simpleOpenGL::obj::Scene;
 use simpleOpenGL::obj::Object;
 use simpleOpenGL::colors::Colors;
 
-const FILE_OUTPUT_PATH:&str="image.tga";
-
 const HEAD_OBJ_PATH:&str="objs/african_head.obj";
 const HEAD_TEXTURE_PATH:&str="objs/diff_text.tga";
 const HEAD_NORMAL_PATH:&str="objs/norm_map.tga";
@@ -28,7 +26,7 @@ const FLOOR_NORMAL_PATH:&str="floor_nm.tga";
 
 const SIZE:usize=500;
 
-fn get_scene() ->Scene{
+fn get_scene() ->Result<Scene,String>{
     let head_texture=read_texture_file(HEAD_TEXTURE_PATH,Colors::RGBA).unwrap();
     let head_nm=read_texture_file(HEAD_NORMAL_PATH,Colors::RGBA).unwrap();
     let head_sp=read_texture_file(HEAD_SP_PATH,Colors::Gray).unwrap();
@@ -46,16 +44,18 @@ fn get_scene() ->Scene{
         .set_text_map(head_texture)
         .set_norm_map(head_nm)
         .set_sp_map(head_sp)
-        .build(HEAD_OBJ_PATH);
+        .build(HEAD_OBJ_PATH)
+        .map_err(|e| format!("{}: {}",HEAD_OBJ_PATH,e))?;
     let eyes =Object::new(position)
         .set_text_map(eye_texture)
         .set_norm_map(eye_nm)
-        .build(EYE_OBJ_PATH);
+        .build(EYE_OBJ_PATH)
+        .map_err(|e| format!("{}: {}",EYE_OBJ_PATH,e))?;
 
     scene.add_obj(head);
     scene.add_obj(eyes);
 
-    scene
+    Ok(scene)
 }
 
 
@@ -81,9 +81,12 @@ pub fn main() -> Result<(), String> {
     let mut texture = texture_creator.create_texture_streaming(PixelFormatEnum::RGBA32, SIZE as u32, SIZE as u32)
         .map_err(|e| e.to_string())?;
 
-    let mut scene= get_scene();
+    let mut scene= get_scene()?;
 
-    let mut buff=scene.draw().as_vec();
+    //the stats of the last frame stand in for an overlay in the title bar
+    let stats=scene.draw();
+    canvas.window_mut().set_title(&stats.to_string()).map_err(|e| e.to_string())?;
+    let mut buff=scene.image().as_vec();
 
     texture.with_lock(None, move|buffer: &mut [u8] , _pitch: usize|
         buffer.swap_with_slice(buff[..buffer.len()].as_mut())
@@ -122,7 +125,9 @@ pub fn main() -> Result<(), String> {
                         },
                         _ => {}
                     }
-                    let mut buff=scene.draw().as_vec();
+                    let stats=scene.draw();
+                    canvas.window_mut().set_title(&stats.to_string()).map_err(|e| e.to_string())?;
+                    let mut buff=scene.image().as_vec();
                     texture.with_lock(None, move|buffer: &mut [u8] , _pitch: usize|
                         buffer.swap_with_slice(buff[..buffer.len()].as_mut())
                     )?;
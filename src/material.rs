@@ -0,0 +1,36 @@
+use crate::dimensional::Vector;
+use crate::texture::Texture;
+use crate::texture::Sampler;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct Material{
+    pub name:String,
+    //ambient reflectance (Ka), multiplied by the ambient light of the scene
+    pub ambient:Vector<f32>,
+    pub diffuse:Vector<f32>,
+    pub specular:Vector<f32>,
+    //specular exponent, used when there is no specular map
+    pub shininess:f32,
+    pub dissolve:f32,
+    pub text_map:Option<Arc<Texture>>,
+    pub norm_map:Option<Arc<Texture>>,
+    pub sp_map:Option<Arc<Texture>>,
+    //filtering and wrapping for every map
+    pub sampler:Sampler,
+}
+
+impl Material{
+    pub fn new(name:&str)->Material{
+        Material{name:String::from(name),ambient:Vector::new(1.,1.,1.),diffuse:Vector::new(1.,1.,1.)
+            ,specular:Vector::new(0.6,0.6,0.6)
+            ,shininess:0.,dissolve:1.,text_map:None,norm_map:None,sp_map:None,sampler:Sampler::default()}
+    }
+}
+
+//white and untextured, what faces without a usable material are drawn with
+impl Default for Material{
+    fn default()->Self{
+        Material::new("")
+    }
+}
@@ -1,6 +1,9 @@
 use crate::dimensional::Vector;
 use std::ops::Index;
 use std::ops::IndexMut;
+use std::ops::Mul;
+use std::ops::Add;
+use std::ops::Sub;
 
 #[derive(Clone)]
 pub struct Matrix{
@@ -45,48 +48,49 @@ impl Matrix{
         response
     }
 
-    pub fn inverse(&self)->Matrix{
+    //Gauss-Jordan with partial pivoting, None when the matrix is singular
+    pub fn inverse(&self)->Option<Matrix>{
         assert_eq!(self.col,self.rows);
-        let mut result =Matrix::new(self.rows, self.col*2);
-        for i in 0..self.rows{
-            for j in 0..self.col{
-                result[i][j]=self[i][j];
-            }
-        }
-        for i in 0..self.rows{
-            result[i][self.col+i]=1.;
-        }
-        for i in 0..self.rows-1{
-            for j in 0..result.col{
-                let borrowed=result.col;
-                result[i][borrowed-1-j]/=result[i][i];
-            }
-            for j in i+1..self.rows{
-                let coeff=result[j][i];
-                for k in 0..result.col{
-                    result[j][k]-=result[i][k]*coeff;
-                }
-            }
+        let n=self.rows;
+        let mut augmented=vec![0.;n*2*n];
+        for i in 0..n{
+            augmented[i*2*n..i*2*n+n].copy_from_slice(&self[i]);
+            augmented[i*2*n+n+i]=1.;
         }
-        for i in 1..&result.col-&result.rows+1{
-            let borrow=result.col;
-            result[self.rows-1][borrow-i]/=result[self.rows-1][self.rows-1];
+        if !gauss_jordan(&mut augmented,n){ return None }
+        let mut inverse=Matrix::new(n,n);
+        for i in 0..n{
+            inverse[i].copy_from_slice(&augmented[i*2*n+n..(i+1)*2*n]);
         }
-        for i in 1..result.rows{
-            for k in 0..i{
-                let coeff=result[i-k-1][result.rows-i];
-                for j in 0..result.col{
-                    result[i-k-1][j]-=result[result.rows-i][j]*coeff;
-                }
-            }
+        Some(inverse)
+    }
+
+    pub fn determinant(&self)->f32{
+        assert_eq!(self.col,self.rows);
+        let mut flat=self.matrix.concat();
+        determinant(&mut flat,self.rows)
+    }
+
+    //transposed cofactors, defined for singular matrices too
+    pub fn adjugate(&self)->Matrix{
+        assert_eq!(self.col,self.rows);
+        let n=self.rows;
+        let mut adjugate=Matrix::new(n,n);
+        if n==1{
+            adjugate[0][0]=1.;
+            return adjugate
         }
-        let mut truncate =Matrix::new(self.rows, self.col);
-        for i in 0..truncate.rows{
-            for j in 0..truncate.col{
-                truncate[i][j]=result[i][j+self.col];
+        for i in 0..n{
+            for j in 0..n{
+                let mut minor=self.matrix.iter().enumerate()
+                    .filter(|(row,_)| *row!=i)
+                    .flat_map(|(_,row)| row.iter().enumerate().filter(|(col,_)| *col!=j).map(|(_,val)| *val))
+                    .collect::<Vec<f32>>();
+                let sign=if (i+j)%2==0{1.}else{-1.};
+                adjugate[j][i]=sign*determinant(&mut minor,n-1);
             }
         }
-        truncate
+        adjugate
     }
 
     pub fn to_vector(&self)->Vector<f32>{
@@ -162,6 +166,61 @@ impl Matrix {
     }
 }
 
+//largest pivot relative to the largest entry that still counts as singular
+const SINGULAR:f32=1e-6;
+
+//reduces a row-major n x 2n augmented matrix to [I|A^-1], false when a pivot vanishes
+fn gauss_jordan(augmented:&mut [f32],n:usize)->bool{
+    let width=2*n;
+    let scale=(0..n).flat_map(|i| augmented[i*width..i*width+n].iter()).fold(0f32,|max,val| max.max(val.abs()));
+    if scale==0.{ return false }
+    for col in 0..n{
+        let pivot=(col..n).max_by(|&a,&b| augmented[a*width+col].abs().total_cmp(&augmented[b*width+col].abs())).unwrap();
+        let largest=augmented[pivot*width+col].abs();
+        if largest.is_nan() || largest<=SINGULAR*scale{ return false }
+        if pivot!=col{
+            for k in 0..width{
+                augmented.swap(pivot*width+k,col*width+k);
+            }
+        }
+        let inv=1./augmented[col*width+col];
+        for k in 0..width{
+            augmented[col*width+k]*=inv;
+        }
+        for row in (0..n).filter(|&row| row!=col){
+            let coeff=augmented[row*width+col];
+            if coeff==0.{ continue }
+            for k in 0..width{
+                augmented[row*width+k]-=coeff*augmented[col*width+k];
+            }
+        }
+    }
+    true
+}
+
+//row-major n x n, destroyed by the elimination
+fn determinant(matrix:&mut [f32],n:usize)->f32{
+    let mut det=1.;
+    for col in 0..n{
+        let pivot=(col..n).max_by(|&a,&b| matrix[a*n+col].abs().total_cmp(&matrix[b*n+col].abs())).unwrap();
+        if matrix[pivot*n+col]==0.{ return 0. }
+        if pivot!=col{
+            for k in 0..n{
+                matrix.swap(pivot*n+k,col*n+k);
+            }
+            det = -det;
+        }
+        det*=matrix[col*n+col];
+        for row in col+1..n{
+            let coeff=matrix[row*n+col]/matrix[col*n+col];
+            for k in col..n{
+                matrix[row*n+k]-=coeff*matrix[col*n+k];
+            }
+        }
+    }
+    det
+}
+
 impl Index<usize> for Matrix{
     type Output = Vec<f32>;
 
@@ -174,4 +233,420 @@ impl IndexMut<usize> for Matrix{
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
             & mut self.matrix[index]
     }
-}
\ No newline at end of file
+}
+
+//homogeneous coordinates, w is 1 for points and 0 for directions
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct Vec4{
+    pub x:f32,
+    pub y:f32,
+    pub z:f32,
+    pub w:f32,
+}
+
+impl Vec4{
+    pub fn new(x:f32,y:f32,z:f32,w:f32)->Vec4{
+        Vec4{x,y,z,w}
+    }
+    pub fn point(vector:&Vector<f32>)->Vec4{
+        Vec4::new(vector.x,vector.y,vector.z,1.)
+    }
+    pub fn direction(vector:&Vector<f32>)->Vec4{
+        Vec4::new(vector.x,vector.y,vector.z,0.)
+    }
+    //drops w without dividing
+    pub fn xyz(&self)->Vector<f32>{
+        Vector::new(self.x,self.y,self.z)
+    }
+    //perspective divide
+    pub fn project(&self)->Vector<f32>{
+        Vector::new(self.x/self.w,self.y/self.w,self.z/self.w)
+    }
+    pub fn dot(&self,other:&Vec4)->f32{
+        self.x*other.x+self.y*other.y+self.z*other.z+self.w*other.w
+    }
+}
+
+impl Add for Vec4{
+    type Output = Vec4;
+
+    fn add(self, rhs: Vec4) -> Self::Output {
+        Vec4::new(self.x+rhs.x,self.y+rhs.y,self.z+rhs.z,self.w+rhs.w)
+    }
+}
+
+impl Sub for Vec4{
+    type Output = Vec4;
+
+    fn sub(self, rhs: Vec4) -> Self::Output {
+        Vec4::new(self.x-rhs.x,self.y-rhs.y,self.z-rhs.z,self.w-rhs.w)
+    }
+}
+
+impl Mul<f32> for Vec4{
+    type Output = Vec4;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Vec4::new(self.x*rhs,self.y*rhs,self.z*rhs,self.w*rhs)
+    }
+}
+
+impl Index<usize> for Vec4{
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0=>&self.x,
+            1=>&self.y,
+            2=>&self.z,
+            _=>&self.w,
+        }
+    }
+}
+
+impl IndexMut<usize> for Vec4{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        match index {
+            0=>&mut self.x,
+            1=>&mut self.y,
+            2=>&mut self.z,
+            _=>&mut self.w,
+        }
+    }
+}
+
+//row-major 4x4 on the stack, m[row][col] like Matrix
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct Matrix4{
+    pub m:[[f32;4];4],
+}
+
+impl Matrix4{
+    pub fn zero()->Matrix4{
+        Matrix4{m:[[0.;4];4]}
+    }
+    pub fn ident()->Matrix4{
+        let mut matrix=Matrix4::zero();
+        for i in 0..4{
+            matrix[i][i]=1.;
+        }
+        matrix
+    }
+    pub fn transpose(&self)->Matrix4{
+        let mut res=Matrix4::zero();
+        for i in 0..4{
+            for j in 0..4{
+                res[j][i]=self[i][j];
+            }
+        }
+        res
+    }
+    pub fn row(&self,index:usize)->Vec4{
+        let row=self.m[index];
+        Vec4::new(row[0],row[1],row[2],row[3])
+    }
+    pub fn inverse(&self)->Option<Matrix4>{
+        let mut augmented=[0.;32];
+        for i in 0..4{
+            augmented[i*8..i*8+4].copy_from_slice(&self[i]);
+            augmented[i*8+4+i]=1.;
+        }
+        if !gauss_jordan(&mut augmented,4){ return None }
+        let mut inverse=Matrix4::zero();
+        for i in 0..4{
+            inverse[i].copy_from_slice(&augmented[i*8+4..i*8+8]);
+        }
+        Some(inverse)
+    }
+    //cheaper for rotations, scales and translations, any other matrix takes the general inverse
+    pub fn affine_inverse(&self)->Option<Matrix4>{
+        let m=&self.m;
+        if m[3]!=[0.,0.,0.,1.]{
+            return self.inverse()
+        }
+        //cofactors of the linear 3x3 part
+        let cof=|r0:usize,r1:usize,c0:usize,c1:usize| m[r0][c0]*m[r1][c1]-m[r0][c1]*m[r1][c0];
+        let adj=[[cof(1,2,1,2),-cof(0,2,1,2),cof(0,1,1,2)],
+            [-cof(1,2,0,2),cof(0,2,0,2),-cof(0,1,0,2)],
+            [cof(1,2,0,1),-cof(0,2,0,1),cof(0,1,0,1)]];
+        let det=m[0][0]*adj[0][0]+m[0][1]*adj[1][0]+m[0][2]*adj[2][0];
+        let scale=(0..3).flat_map(|i| m[i][..3].iter()).fold(0f32,|max,val| max.max(val.abs()));
+        if det.is_nan() || det.abs()<=SINGULAR*scale*scale*scale{ return None }
+        let mut inverse=Matrix4::ident();
+        for i in 0..3{
+            for j in 0..3{
+                inverse[i][j]=adj[i][j]/det;
+            }
+            inverse[i][3] = -(inverse[i][0]*m[0][3]+inverse[i][1]*m[1][3]+inverse[i][2]*m[2][3]);
+        }
+        Some(inverse)
+    }
+    //inverse-transpose of the linear part, carries normals into the space the matrix maps to
+    //so they stay perpendicular under non-uniform scale
+    pub fn normal_matrix(&self)->Matrix4{
+        match self.affine_inverse() {
+            Some(inverse)=>inverse.transpose(),
+            None=>*self,
+        }
+    }
+    pub fn determinant(&self)->f32{
+        let mut flat=[0.;16];
+        for i in 0..4{
+            flat[i*4..i*4+4].copy_from_slice(&self[i]);
+        }
+        determinant(&mut flat,4)
+    }
+    pub fn adjugate(&self)->Matrix4{
+        Matrix4::from(&Matrix::from(*self).adjugate())
+    }
+    pub fn transform_point(&self,point:&Vector<f32>)->Vector<f32>{
+        (*self*Vec4::point(point)).project()
+    }
+    pub fn transform_direction(&self,direction:&Vector<f32>)->Vector<f32>{
+        (*self*Vec4::direction(direction)).xyz()
+    }
+
+    pub fn zoom(zoom:f32)->Matrix4{
+        let mut matrix=Matrix4::ident();
+        matrix[0][0]=zoom;
+        matrix[1][1]=zoom;
+        matrix[2][2]=zoom;
+        matrix
+    }
+    pub fn translation(vector:&Vector<f32>)->Matrix4{
+        let mut matrix=Matrix4::ident();
+        matrix[0][3]=vector.x;
+        matrix[1][3]=vector.y;
+        matrix[2][3]=vector.z;
+        matrix
+    }
+    //same axes as Matrix::rotate_x, _y and _z
+    pub fn rotate_x(cos:f32,sin:f32)->Matrix4{
+        let mut matrix=Matrix4::ident();
+        matrix[0][0]=cos;
+        matrix[2][2]=cos;
+        matrix[0][2] = -sin;
+        matrix[2][0]=sin;
+        matrix
+    }
+    pub fn rotate_y(cos:f32,sin:f32)->Matrix4{
+        let mut matrix=Matrix4::ident();
+        matrix[1][1]=cos;
+        matrix[2][2]=cos;
+        matrix[1][2] = -sin;
+        matrix[2][1]=sin;
+        matrix
+    }
+    pub fn rotate_z(cos:f32,sin:f32)->Matrix4{
+        let mut matrix=Matrix4::ident();
+        matrix[1][1]=cos;
+        matrix[0][0]=cos;
+        matrix[0][1] = -sin;
+        matrix[1][0]=sin;
+        matrix
+    }
+    //right-handed, camera looks down -z; an eye on the center looks down -z of the world and
+    //an up along the view direction is swapped for a world axis, so the result is never NaN
+    pub fn look_at(eye:&Vector<f32>,center:&Vector<f32>,up:&Vector<f32>)->Matrix4{
+        let offset=*eye-*center;
+        let z_vec=if offset.length()>0.{offset.normalize()}else{Vector::new(0.,0.,1.)};
+        //a zero up normalizes to NaN and is not usable either
+        let usable=|up:&Vector<f32>| z_vec.scalar_prod(&up.normalize()).abs()<=0.99;
+        let up=if usable(up){*up}
+            else if usable(&Vector::new(0.,1.,0.)){Vector::new(0.,1.,0.)}
+            else{Vector::new(1.,0.,0.)};
+        let x=up.vector_prod(z_vec).normalize();
+        let y=z_vec.vector_prod(x);
+        let (mut camera_view,mut trans_matrix)=(Matrix4::ident(),Matrix4::ident());
+        for i in 0..3{
+            camera_view[0][i]=x[i];
+            camera_view[1][i]=y[i];
+            camera_view[2][i]=z_vec[i];
+            trans_matrix[i][3] = -eye[i];
+        }
+        camera_view*trans_matrix
+    }
+    //fov_y in radians, maps the view frustum to the [-1,1] cube
+    pub fn perspective(fov_y:f32,aspect:f32,near:f32,far:f32)->Matrix4{
+        let focal=1./(fov_y/2.).tan();
+        let mut matrix=Matrix4::zero();
+        matrix[0][0]=focal/aspect;
+        matrix[1][1]=focal;
+        matrix[2][2]=(far+near)/(near-far);
+        matrix[2][3]=2.*far*near/(near-far);
+        matrix[3][2] = -1.;
+        matrix
+    }
+    pub fn orthographic(left:f32,right:f32,bottom:f32,top:f32,near:f32,far:f32)->Matrix4{
+        let mut matrix=Matrix4::ident();
+        matrix[0][0]=2./(right-left);
+        matrix[1][1]=2./(top-bottom);
+        matrix[2][2]=-2./(far-near);
+        matrix[0][3]=-(right+left)/(right-left);
+        matrix[1][3]=-(top+bottom)/(top-bottom);
+        matrix[2][3]=-(far+near)/(far-near);
+        matrix
+    }
+}
+
+impl Mul for Matrix4{
+    type Output = Matrix4;
+
+    fn mul(self, rhs: Matrix4) -> Self::Output {
+        let mut res=Matrix4::zero();
+        for i in 0..4{
+            for j in 0..4{
+                res[i][j]=self[i][0]*rhs[0][j]+self[i][1]*rhs[1][j]+self[i][2]*rhs[2][j]+self[i][3]*rhs[3][j];
+            }
+        }
+        res
+    }
+}
+
+impl Mul<Vec4> for Matrix4{
+    type Output = Vec4;
+
+    fn mul(self, rhs: Vec4) -> Self::Output {
+        Vec4::new(self.row(0).dot(&rhs),self.row(1).dot(&rhs),self.row(2).dot(&rhs),self.row(3).dot(&rhs))
+    }
+}
+
+impl Index<usize> for Matrix4{
+    type Output = [f32;4];
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.m[index]
+    }
+}
+
+impl IndexMut<usize> for Matrix4{
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.m[index]
+    }
+}
+
+impl From<&Matrix> for Matrix4{
+    //the top left 4x4 block, missing entries are taken from the identity
+    fn from(matrix: &Matrix) -> Self {
+        let mut res=Matrix4::ident();
+        for i in 0..matrix.rows.min(4){
+            for j in 0..matrix.col.min(4){
+                res[i][j]=matrix[i][j];
+            }
+        }
+        res
+    }
+}
+
+impl From<Matrix4> for Matrix{
+    fn from(matrix: Matrix4) -> Self {
+        Matrix{matrix:matrix.m.iter().map(|row| row.to_vec()).collect(),rows:4,col:4}
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn assert_close(a:&Matrix4,b:&Matrix4){
+        for i in 0..4{
+            for j in 0..4{
+                assert!((a[i][j]-b[i][j]).abs()<1e-4,"{:?} != {:?}",a,b);
+            }
+        }
+    }
+
+    fn model()->Matrix4{
+        let mut scale=Matrix4::ident();
+        scale[0][0]=2.;
+        scale[1][1]=0.5;
+        scale[2][2]=3.;
+        let (cos,sin)=(0.3f32.cos(),0.3f32.sin());
+        Matrix4::translation(&Vector::new(1.,-2.,5.))*Matrix4::rotate_x(cos,sin)*scale
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix(){
+        let projection=Matrix4::perspective(0.8,1.5,0.1,100.)*model();
+        for matrix in [model(),projection]{
+            let inverse=matrix.inverse().unwrap();
+            assert_close(&(matrix*inverse),&Matrix4::ident());
+            assert_close(&(inverse*matrix),&Matrix4::ident());
+        }
+    }
+
+    #[test]
+    fn inverse_pivots_around_zero_diagonals(){
+        //a permutation has only zeros on its diagonal
+        let mut swap=Matrix4::zero();
+        swap[0][1]=1.;
+        swap[1][0]=1.;
+        swap[2][3]=1.;
+        swap[3][2]=1.;
+        assert_close(&swap.inverse().unwrap(),&swap);
+        let mut general=Matrix::new(3,3);
+        general.matrix=vec![vec![0.,2.,1.],vec![1.,0.,0.],vec![3.,1.,0.]];
+        let inverse=general.inverse().unwrap();
+        let product=general.multiply(&inverse);
+        for i in 0..3{
+            for j in 0..3{
+                assert!((product[i][j]-if i==j{1.}else{0.}).abs()<1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse(){
+        let mut rank_two=model();
+        rank_two.m[2]=rank_two.m[1];
+        assert!(rank_two.inverse().is_none());
+        assert!(Matrix4::zero().inverse().is_none());
+        let mut flat=Matrix4::ident();
+        flat[1][1]=0.;
+        assert!(flat.inverse().is_none());
+        assert!(flat.affine_inverse().is_none());
+        assert!(Matrix::from(flat).inverse().is_none());
+        //tiny but regular stays invertible, the threshold is relative to the entries
+        let mut tiny=Matrix4::zero();
+        for i in 0..4{
+            tiny[i][i]=1e-8;
+        }
+        assert!(tiny.inverse().is_some());
+    }
+
+    #[test]
+    fn affine_inverse_matches_inverse(){
+        assert_close(&model().affine_inverse().unwrap(),&model().inverse().unwrap());
+        //a projective bottom row takes the general path
+        let projection=Matrix4::perspective(0.8,1.5,0.1,100.)*model();
+        assert_close(&projection.affine_inverse().unwrap(),&projection.inverse().unwrap());
+    }
+
+    #[test]
+    fn determinant_and_adjugate(){
+        let matrix=model();
+        assert!((matrix.determinant()-3.).abs()<1e-4);
+        let mut scaled=matrix.adjugate();
+        for row in scaled.m.iter_mut(){
+            for val in row.iter_mut(){
+                *val/=matrix.determinant();
+            }
+        }
+        assert_close(&scaled,&matrix.inverse().unwrap());
+    }
+
+    #[test]
+    fn look_at_survives_degenerate_cameras(){
+        let origin=Vector::new(0.,0.,0.);
+        let up=Vector::new(0.,1.,0.);
+        let cameras=[(origin,origin,up),(Vector::new(0.,3.,0.),origin,up),
+                     (Vector::new(0.,-3.,0.),origin,up),(Vector::new(0.,0.,3.),origin,Vector::new(0.,0.,-2.))];
+        for (eye,center,up) in cameras.iter(){
+            let view=Matrix4::look_at(eye,center,up);
+            assert!(view.m.iter().flatten().all(|val| val.is_finite()),"{:?}",view);
+            //the center still ends up straight ahead of the camera
+            let ahead=view*Vec4::point(center);
+            assert!(ahead.x.abs()<1e-4 && ahead.y.abs()<1e-4 && ahead.z<=0.);
+        }
+    }
+}
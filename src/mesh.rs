@@ -0,0 +1,85 @@
+use crate::dimensional::Vector;
+use crate::shader::Shader;
+use crate::shader::Uniforms;
+use crate::shader::Vertex;
+use crate::clip::ClipVertex;
+
+//object-space geometry shared between objects and frames and never touched by drawing,
+//a vertex is stored once however many triangles use it
+#[derive(Clone,Default)]
+pub struct Mesh{
+    positions:Vec<Vector<f32>>,
+    text_coords:Vec<Vector<f32>>,
+    normals:Vec<Vector<f32>>,
+    //three vertex indices per triangle
+    indices:Vec<[usize;3]>,
+    //per triangle index into the materials of the object, None is the object's own material
+    materials:Vec<Option<usize>>,
+}
+
+impl Mesh{
+    pub fn new()->Mesh{
+        Mesh::default()
+    }
+
+    //returns the index of the new vertex
+    pub fn push_vertex(&mut self,position:Vector<f32>,text_coord:Vector<f32>,normal:Vector<f32>)->usize{
+        self.positions.push(position);
+        self.text_coords.push(text_coord);
+        self.normals.push(normal);
+        self.positions.len()-1
+    }
+    pub fn push_triangle(&mut self,indices:[usize;3],material:Option<usize>){
+        assert!(indices.iter().all(|&index| index<self.positions.len()),"triangle {:?} uses a missing vertex",indices);
+        self.indices.push(indices);
+        self.materials.push(material);
+    }
+
+    pub fn positions(&self)->&[Vector<f32>]{
+        &self.positions
+    }
+    pub fn vertex(&self,index:usize)->Vertex{
+        Vertex{position:self.positions[index],text_coord:self.text_coords[index],normal:self.normals[index]}
+    }
+    pub fn vertex_count(&self)->usize{
+        self.positions.len()
+    }
+    pub fn triangle(&self,index:usize)->[usize;3]{
+        self.indices[index]
+    }
+    pub fn material(&self,index:usize)->Option<usize>{
+        self.materials[index]
+    }
+    //number of triangles
+    pub fn len(&self)->usize{
+        self.indices.len()
+    }
+    pub fn is_empty(&self)->bool{
+        self.indices.is_empty()
+    }
+}
+
+//post-transform cache: the output of the vertex shader per mesh vertex, a vertex is only
+//shaded again when a triangle with another draw call uses it
+#[derive(Default)]
+pub struct VertexCache{
+    entries:Vec<Option<(usize,ClipVertex)>>,
+}
+
+impl VertexCache{
+    //forgets everything and makes room for a mesh of len vertices, keeps the allocation
+    pub fn reset(&mut self,len:usize){
+        self.entries.clear();
+        self.entries.resize(len,None);
+    }
+
+    pub fn get(&mut self,mesh:&Mesh,index:usize,draw:usize,shader:&dyn Shader,uniforms:&Uniforms)->ClipVertex{
+        if let Some((cached_draw,vertex))=self.entries[index]{
+            if cached_draw==draw{ return vertex }
+        }
+        let (position,varyings)=shader.vertex(uniforms,&mesh.vertex(index));
+        let vertex=ClipVertex{position,varyings};
+        self.entries[index]=Some((draw,vertex));
+        vertex
+    }
+}
@@ -1,108 +1,221 @@
-#![feature(duration_as_u128)]
-
 use crate::texture::Texture;
 use crate::dimensional::Vector;
 use crate::file_input;
-use crate::matrix::Matrix;
+use crate::file_input::ObjError;
+use crate::matrix::Matrix4;
 use crate::plane::TGAImage;
-use std::thread;
+use crate::shader::Shader;
+use crate::shader::Shading;
+use crate::shader::DefaultShader;
+use crate::shader::Uniforms;
+use crate::material::Material;
+use crate::camera::Camera;
+use crate::light::Light;
+use crate::shadow;
+use crate::shadow::ShadowMap;
+use crate::shadow::ShadowSettings;
+use crate::shadow::DepthShader;
+use crate::ssao;
+use crate::ssao::SsaoSettings;
+use crate::clip;
+use crate::clip::ClipVertex;
+use crate::mesh::Mesh;
+use crate::mesh::VertexCache;
+use crate::raster;
+use crate::raster::DrawCall;
+use crate::raster::ScreenTriangle;
+use crate::pool::ThreadPool;
+use crate::stats::RenderStats;
+use crate::stats::TriangleStats;
+use crate::colors::Colors;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::ops::Range;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
 
 
 const MIN_ON_THREAD:usize=200;
-const NUM_OF_THREAD:usize=4;
 
 #[derive(Clone)]
-pub struct Poly{
-    coords:Vec<Vector<f32>>,
-    text_coords:Vec<Vector<f32>>,
-    norm_coords:Vec<Vector<f32>>,
-}
-impl Poly {
-    pub fn new(tulp:(Vec<Vector<f32>>, Vec<Vector<f32>>,Vec<Vector<f32>>))-> Poly {
-        Poly{coords:tulp.0,text_coords:tulp.1,norm_coords:tulp.2}
-    }
-    fn draw_self(&mut self, image:& Arc<TGAImage>, light: &Vector<f32>,
-                 text_map:&Option<Arc<Texture>>, norm_map:&Option<Arc<Texture>>,sp_map:&Option<Arc<Texture>>){
-
-        image.fill_triangle(light,self.coords.as_mut_slice(),self.text_coords.as_mut_slice(),text_map
-                            ,self.norm_coords.as_mut_slice(),norm_map,sp_map);
-    }
-}
-
 pub struct Object{
-    polygons:Vec<Poly>,
+    mesh:Arc<Mesh>,
     position:Vector<f32>,
-    mod_matrix:Option<Matrix>,
-    text_map:Option<Arc<Texture>>,
-    norm_map:Option<Arc<Texture>>,
-    sp_map:Option<Arc<Texture>>,
-    pointer:usize,
+    mod_matrix:Option<Matrix4>,
+    material:Material,
+    materials:Vec<Arc<Material>>,
+    shader:Arc<dyn Shader>,
 }
 
 impl Object{
     pub fn new(position:Vector<f32>)->Object{
-        let polygons=Vec::new();
-        Object{polygons, mod_matrix:None,position,text_map:None,norm_map:None,sp_map:None,pointer:0}
+        Object{mesh:Arc::new(Mesh::default()), mod_matrix:None,position,material:Material::new(""),materials:Vec::new()
+            ,shader:Arc::new(DefaultShader::new(Shading::Phong))}
     }
 
     pub fn set_text_map(mut self,text_map:Arc<Texture>)->Self{
-        self.text_map=Some(text_map);
+        self.material.text_map=Some(text_map);
         self
     }
     pub fn set_norm_map(mut self,norm_map:Arc<Texture>)->Self{
-        self.norm_map=Some(norm_map);
+        self.material.norm_map=Some(norm_map);
         self
     }
     pub fn set_sp_map(mut self,sp_map:Arc<Texture>)->Self{
-        self.sp_map=Some(sp_map);
+        self.material.sp_map=Some(sp_map);
+        self
+    }
+    pub fn set_material(mut self,material:Material)->Self{
+        self.material=material;
+        self
+    }
+    pub fn set_shading(mut self,shading:Shading)->Self{
+        self.shader=Arc::new(DefaultShader::new(shading));
         self
     }
+    pub fn set_shader(mut self,shader:Arc<dyn Shader>)->Self{
+        self.shader=shader;
+        self
+    }
+    //objects sharing a mesh draw the same geometry without copying it
+    pub fn set_mesh(mut self,mesh:Arc<Mesh>)->Self{
+        self.mesh=mesh;
+        self
+    }
+    pub fn mesh(&self)->&Arc<Mesh>{
+        &self.mesh
+    }
     pub fn set_position(&mut self,position:Vector<f32>){
         self.position=position;
     }
 
-    pub fn build(mut self, file_path:&str)->Self{
-        let triangles=file_input::read_file(file_path);
-        for mut triangle in triangles{
-            if let Some(text_map)=&self.text_map{
-                triangle.1=triangle.1
-                    .iter()
-                    .map(|element| Vector::new(element.x*text_map.width as f32,
-                                               element.y*text_map.height as f32,0.))
-                    .collect::<Vec<Vector<f32>>>();
+    //mtllib and texture paths are resolved relative to the directory of the obj file, a missing
+    //mtllib or an undefined usemtl draws with the default material after a warning
+    pub fn build(mut self, file_path:&str)->Result<Self,ObjError>{
+        let obj_file=file_input::read_file(file_path)?;
+        let dir=Path::new(file_path).parent().unwrap_or_else(|| Path::new(""));
+
+        let mut textures=HashMap::new();
+        let mut library=Vec::new();
+        for lib in obj_file.material_libs.iter(){
+            let path=dir.join(lib).to_string_lossy().into_owned();
+            let entries=match file_input::read_mtl_file(&path) {
+                Ok(entries)=>entries,
+                Err(ObjError::Io(ref error)) if error.kind()==io::ErrorKind::NotFound=>{
+                    eprintln!("warning: {}: {}",path,error);
+                    continue
+                },
+                Err(error)=>return Err(ObjError::Material{path,error:Box::new(error)}),
             };
-            if let Some(norm_map)=&self.norm_map {
-                triangle.2 = triangle.2.iter()
-                    .map(|element| Vector::new(element.x * norm_map.width as f32,
-                                               element.y * norm_map.height as f32, 0.))
-                    .collect::<Vec<Vector<f32>>>();
+            for entry in entries{
+                let mut material=entry.material;
+                material.text_map=load_texture(&mut textures,dir,&entry.text_map,Colors::RGBA)?;
+                material.norm_map=load_texture(&mut textures,dir,&entry.norm_map,Colors::RGBA)?;
+                material.sp_map=load_texture(&mut textures,dir,&entry.sp_map,Colors::Gray)?;
+                library.push(material);
+            }
+        }
+        let base=self.materials.len();
+        for (line,name) in obj_file.material_names{
+            let material=match library.iter().find(|material| material.name==name) {
+                Some(material)=>material.clone(),
+                None=>{
+                    eprintln!("warning: {}: {}",file_path,ObjError::UnknownMaterial{line,name});
+                    Material::default()
+                },
             };
-            let poly = Poly::new(triangle);
-            self.polygons.push(poly);
+            self.materials.push(Arc::new(material));
         }
-        self
+
+        //copies the mesh only when another object shares it
+        let mesh=Arc::make_mut(&mut self.mesh);
+        let first=mesh.vertex_count();
+        for ((position,text_coord),normal) in obj_file.positions.into_iter().zip(obj_file.text_coords).zip(obj_file.normals){
+            mesh.push_vertex(position,text_coord,normal);
+        }
+        for (triangle,material) in obj_file.triangles.into_iter().zip(obj_file.materials){
+            let material=material.map(|index| base+index);
+            mesh.push_triangle([first+triangle[0],first+triangle[1],first+triangle[2]],material);
+        }
+        Ok(self)
     }
 
-    fn from_obj(&self,first:usize,last:usize)->Object{
-        let polygons=self.polygons[first..last].to_vec();
+    //object to world space
+    fn model_matrix(&self)->Matrix4{
+        let translation=Matrix4::translation(&self.position);
+        match self.mod_matrix {
+            Some(mat)=>translation*mat,
+            None=>translation,
+        }
+    }
 
-        Object{polygons,  mod_matrix:self.mod_matrix.clone()
-            ,position:self.position,    text_map:self.text_map.clone()
-            ,norm_map:self.norm_map.clone(),sp_map:self.sp_map.clone(),pointer:0}
+    //the material of a draw call slot, 0 is the object's own material and i+1 the i-th of materials
+    fn slot_material(&self,slot:usize)->&Material{
+        match slot {
+            0=>&self.material,
+            slot=>&self.materials[slot-1],
+        }
+    }
+    fn slots(&self)->usize{
+        self.materials.len()+1
     }
 
-    fn draw_self(&mut self,image:Arc<TGAImage>,light: Vector<f32>,sight: Vector<f32>) {
-        for poly in &mut self.polygons{
-            let vec0 = poly.coords[0] - poly.coords[1];
-            let vec1 = poly.coords[0] - poly.coords[2];
+    //one draw call per material slot, shader overrides the object's own
+    fn draw_calls<'a>(&'a self,shader:Option<&'a dyn Shader>,transforms:&'a Transforms,lights:&'a [Light]
+                      ,shadows:&'a [Option<ShadowMap>],eye:Vector<f32>,ambient:Vector<f32>)->impl Iterator<Item=DrawCall<'a>>{
+        let shader=shader.unwrap_or(&*self.shader);
+        (0..self.slots()).map(move |slot| DrawCall{shader
+            ,uniforms:transforms.uniforms(lights,shadows,eye,ambient,self.slot_material(slot))})
+    }
 
-            let triangle_normal = vec0.vector_prod(vec1)
-                .normalize();
-            let intensity = triangle_normal.scalar_prod(&sight);
-            if intensity > 0.0 {
-                poly.draw_self(&image, &light, &self.text_map, &self.norm_map,&self.sp_map);
+    //clips, projects and culls the mesh triangles in range and appends them to job.triangles,
+    //counting them in job.stats, the object's draw calls start at draws[first]
+    fn triangles(&self,range:Range<usize>,draws:&[DrawCall],first:usize,screen:&Screen,job:&mut JobBuffers){
+        let Screen{height,width,sight}=*screen;
+        let mesh=&*self.mesh;
+        job.cache.reset(mesh.vertex_count());
+        job.stats.submitted+=range.len();
+        for index in range{
+            let clipped_buffer;
+            let draw=first+mesh.material(index).map_or(0,|index| index+1);
+            let (shader,uniforms)=(draws[draw].shader,&draws[draw].uniforms);
+            let vertices=mesh.triangle(index).map(|vertex| job.cache.get(mesh,vertex,draw,shader,uniforms));
+            //most polygons are fully visible and skip the clipper
+            let clipped:&[ClipVertex]=if clip::inside(&vertices){
+                &vertices
+            }else{
+                clipped_buffer=clip::clip_polygon(vertices.to_vec());
+                &clipped_buffer
+            };
+            if clipped.len()<3{
+                job.stats.frustum_culled+=1;
+                continue
+            }
+            let screen=|vertex:&ClipVertex| vertex.to_ndc().to_plane(height,width);
+
+            for i in 1..clipped.len()-1{
+                let coords=[screen(&clipped[0]),screen(&clipped[i]),screen(&clipped[i+1])];
+                if let Some(sight)=&sight{
+                    let vec0 = coords[0] - coords[1];
+                    let vec1 = coords[0] - coords[2];
+
+                    let triangle_normal = vec0.vector_prod(vec1)
+                        .normalize();
+                    let intensity = triangle_normal.scalar_prod(sight);
+                    if intensity <= 0.0 {
+                        job.stats.backface_culled+=1;
+                        continue
+                    }
+                }
+                let vertices=[clipped[0],clipped[i],clipped[i+1]];
+                let w=[vertices[0].position.w,vertices[1].position.w,vertices[2].position.w];
+                let varyings=[vertices[0].varyings,vertices[1].varyings,vertices[2].varyings];
+                job.triangles.push(ScreenTriangle{coords,w,varyings,draw});
+                job.stats.rasterized+=1;
             }
         }
     }
@@ -111,171 +224,405 @@ impl Object{
         //degree ffs
         let angle=angle/57.4;
         let (cos,sin)=(angle.cos(),angle.sin());
-        self.mod_matrix=match &self.mod_matrix {
-            None=>Some(Matrix::rotate_x(cos, sin)),
-            Some(mat)=>Some(mat.multiply(&Matrix::rotate_x(cos, sin)))
+        self.mod_matrix=match self.mod_matrix {
+            None=>Some(Matrix4::rotate_x(cos, sin)),
+            Some(mat)=>Some(mat*Matrix4::rotate_x(cos, sin))
         };
         self
     }
     pub fn rotate_y(&mut self,angle:f32)->&mut Self{
         let angle=angle/57.4;
         let (cos,sin)=(angle.cos(),angle.sin());
-        self.mod_matrix=match &self.mod_matrix {
-            None=>Some(Matrix::rotate_x(cos, sin)),
-            Some(mat)=>Some(mat.multiply(&Matrix::rotate_y(cos, sin)))
+        self.mod_matrix=match self.mod_matrix {
+            None=>Some(Matrix4::rotate_y(cos, sin)),
+            Some(mat)=>Some(mat*Matrix4::rotate_y(cos, sin))
         };
         self
     }
     pub fn rotate_z(&mut self,angle:f32)->&mut Self{
         let angle=angle/57.4;
         let (cos,sin)=(angle.cos(),angle.sin());
-        self.mod_matrix=match &self.mod_matrix {
-            None=>Some(Matrix::rotate_x(cos, sin)),
-            Some(mat)=>Some(mat.multiply(&Matrix::rotate_z(cos, sin)))
+        self.mod_matrix=match self.mod_matrix {
+            None=>Some(Matrix4::rotate_z(cos, sin)),
+            Some(mat)=>Some(mat*Matrix4::rotate_z(cos, sin))
         };
         self
     }
 }
 
-impl PortionIterator for Object{
-    type Item = Object;
-
-    fn next(& mut self, portion: usize) -> Option<Self::Item> {
-        if self.pointer>=self.polygons.len(){return None}
-
-        let last_index=if self.pointer+portion>self.polygons.len(){
-            self.polygons.len()
-        }else{
-            self.pointer+portion
-        };
-        let first_index=self.pointer;
-        self.pointer=last_index;
-        Some(self.from_obj(first_index,last_index))
+//matrices of an object for one pass, the uniforms of its draw calls borrow them
+struct Transforms{
+    mvp:Matrix4,
+    model:Matrix4,
+    normal_matrix:Matrix4,
 }
 
-    fn rewind(&mut self) {
-        self.pointer=0;
+impl Transforms{
+    fn new(view_proj:&Matrix4,model:Matrix4)->Transforms{
+        Transforms{mvp:*view_proj*model,model,normal_matrix:model.normal_matrix()}
+    }
+    fn uniforms<'a>(&'a self,lights:&'a [Light],shadows:&'a [Option<ShadowMap>],eye:Vector<f32>,ambient:Vector<f32>
+                    ,material:&'a Material)->Uniforms<'a>{
+        Uniforms{mvp:&self.mvp,model:&self.model,normal_matrix:&self.normal_matrix,lights,shadows,eye,ambient,material}
     }
 }
 
-pub trait PortionIterator{
-    type Item;
+//index of the first draw call of every object
+fn draw_bases(objects:&[Object])->Vec<usize>{
+    objects.iter().scan(0,|next,obj|{
+        let first=*next;
+        *next+=obj.slots();
+        Some(first)
+    }).collect()
+}
 
-    fn next(& mut self,portion:usize)->Option<Self::Item>;
-    fn rewind(&mut self);
+fn draw_calls<'a>(objects:&'a [Object],shader:Option<&'a dyn Shader>,transforms:&'a [Transforms],lights:&'a [Light]
+                  ,shadows:&'a [Option<ShadowMap>],eye:Vector<f32>,ambient:Vector<f32>)->Vec<DrawCall<'a>>{
+    objects.iter().zip(transforms)
+        .flat_map(|(obj,transforms)| obj.draw_calls(shader,transforms,lights,shadows,eye,ambient))
+        .collect()
 }
 
 #[derive(Clone)]
 pub struct SceneContext{
-    view_port:Matrix,
-    projection:Matrix,
-    light:Vector<f32>,
-    eye:Vector<f32>,
-    up:Vector<f32>,
+    view_port:Matrix4,
+    camera:Camera,
+    lights:Vec<Light>,
+    shadow_maps:Arc<Vec<Option<ShadowMap>>>,
     visible:Vector<f32>,
+    //light reaching every surface from everywhere, scaled by the ambient color of materials
+    ambient:Vector<f32>,
+}
+
+impl SceneContext{
+    fn view_proj(&self)->Matrix4{
+        self.view_port*self.camera.projection_matrix()*self.camera.view_matrix()
+    }
 }
 
 pub struct Scene{
     pub objects:Vec<Object>,
-    image:Arc<TGAImage>,
+    image:TGAImage,
     context:SceneContext,
-    total_triangles:usize,
+    shadows:Option<ShadowSettings>,
+    ssao:Option<SsaoSettings>,
+    pool:ThreadPool,
+    buffers:Buffers,
 }
 
 impl Scene{
+    //light is the legacy single light: the direction towards it with z inverted, the way
+    //TGAColor::to_vector decodes normal maps
     pub fn new(height:usize,width:usize,light:Vector<f32>)->Scene{
-        let eye=Vector::new(-1.,-1.,3.);
-        let mut projection=Matrix::ident(4);
-        projection[3][2]=-1./eye.z;
-        let up=Vector::new(0.,1.,0.);
-        let view_port=Matrix::view_port(-2.,-2.,2.,2.);
-        let image=Arc::new(TGAImage::new(height,width));
+        let mut camera=Camera::new(Vector::new(-1.,-1.,3.),Vector::new(0.,0.,0.),width as f32/height as f32);
+        //frames a unit sized model from the default eye
+        camera.fov=35.;
+        //the z-buffer keeps the greatest depth, flip ndc z so the near plane wins
+        let mut view_port=Matrix4::ident();
+        view_port[2][2] = -1.;
+        let image=TGAImage::new(height,width);
         let visible=Vector::new(0.,0.,1.);
-        let context=SceneContext{light,eye,visible,projection,view_port,up,};
-        Scene{objects:Vec::new(),image,context,total_triangles:0}
+        let lights=vec![Light::directional(Vector::new(-light.x,-light.y,light.z))];
+        let ambient=Vector::new(0.05,0.05,0.05);
+        let context=SceneContext{lights,camera,visible,view_port,shadow_maps:Arc::new(Vec::new()),ambient};
+        Scene{objects:Vec::new(),image,context,shadows:None,ssao:None
+            ,pool:ThreadPool::new(thread::available_parallelism().map_or(1,|threads| threads.get())),buffers:Buffers::default()}
+    }
+
+    pub fn camera(&self)->&Camera{
+        &self.context.camera
+    }
+    pub fn camera_mut(&mut self)->&mut Camera{
+        &mut self.context.camera
+    }
+    pub fn set_camera(&mut self,camera:Camera){
+        self.context.camera=camera;
+    }
+
+    pub fn lights(&self)->&[Light]{
+        &self.context.lights
+    }
+    pub fn add_light(&mut self,light:Light){
+        self.context.lights.push(light);
+    }
+    //None when there is no light at index
+    pub fn remove_light(&mut self,index:usize)->Option<Light>{
+        if index>=self.context.lights.len(){ return None }
+        Some(self.context.lights.remove(index))
+    }
+    pub fn clear_lights(&mut self){
+        self.context.lights.clear();
+    }
+    //ambient light intensity per channel, 0.05 by default
+    pub fn set_ambient(&mut self,ambient:Vector<f32>){
+        self.context.ambient=ambient;
+    }
+    pub fn ambient(&self)->Vector<f32>{
+        self.context.ambient
+    }
+
+    //workers drawing a frame, 1 renders on the calling thread; starts at one per core
+    pub fn set_threads(&mut self,threads:usize){
+        if threads.max(1)!=self.pool.threads(){
+            self.pool=ThreadPool::new(threads);
+        }
+    }
+    pub fn threads(&self)->usize{
+        self.pool.threads()
+    }
+
+    //None turns shadows off, lights opt out with Light::set_shadow
+    pub fn set_shadows(&mut self,shadows:Option<ShadowSettings>){
+        self.shadows=shadows;
+    }
+    pub fn shadows(&self)->Option<ShadowSettings>{
+        self.shadows
+    }
+
+    //None turns the ambient occlusion post-process off
+    pub fn set_ssao(&mut self,ssao:Option<SsaoSettings>){
+        self.ssao=ssao;
+    }
+    pub fn ssao(&self)->Option<SsaoSettings>{
+        self.ssao
+    }
+
+    //linear view distance of the last frame per pixel, infinite where nothing was drawn
+    pub fn depth(&self)->Vec<f32>{
+        let width=self.image.width as f32;
+        let camera=&self.context.camera;
+        self.image.depth_buffer().iter().map(|&z|{
+            if z==f32::MIN{ return f32::INFINITY }
+            //undo to_plane and the view port flip
+            camera.linear_depth(1.-2.*z/width)
+        }).collect()
+    }
+
+    //ambient visibility of the last frame, see ssao::ambient_occlusion
+    pub fn ambient_occlusion(&self,settings:&SsaoSettings)->Vec<f32>{
+        ssao::ambient_occlusion(&self.depth(),self.image.height,self.image.width,&self.context.camera,settings)
     }
 
     pub fn add_obj(&mut self,obj:Object){
-        self.total_triangles+=obj.polygons.len();
         self.objects.push(obj);
     }
 
-    pub fn draw(& mut self)->&TGAImage{
-        self.image=Arc::new(TGAImage::new(self.image.height,self.image.width));
-        for obj in self.objects.as_mut_slice(){obj.rewind();}
+    //bounding sphere of every object in world space
+    fn bounds(&self)->(Vector<f32>,f32){
+        let points=self.objects.iter()
+            .flat_map(|obj|{
+                let model=obj.model_matrix();
+                obj.mesh.positions().iter()
+                    .map(move |coord| model.transform_point(coord))
+            })
+            .collect::<Vec<Vector<f32>>>();
+        if points.is_empty(){ return (Vector::new(0.,0.,0.),1.) }
+        let (mut min,mut max)=(points[0],points[0]);
+        for point in points.iter(){
+            min=Vector::new(min.x.min(point.x),min.y.min(point.y),min.z.min(point.z));
+            max=Vector::new(max.x.max(point.x),max.y.max(point.y),max.z.max(point.z));
+        }
+        let center=(min+max)*0.5;
+        let radius=points.iter().map(|point| (*point-center).length()).fold(0.,f32::max);
+        (center,radius)
+    }
 
-        let portion=if self.total_triangles/MIN_ON_THREAD>=NUM_OF_THREAD{
-            self.total_triangles/(NUM_OF_THREAD)
-        }else {
-            MIN_ON_THREAD
+    //depth pass from every shadow casting light
+    fn render_shadow_maps(&mut self)->Vec<Option<ShadowMap>>{
+        let settings=match &self.shadows {
+            Some(settings)=>*settings,
+            None=>return Vec::new(),
         };
-
-        let mut jobs=Vec::new();
-        let mut job=Vec::new();
-        for obj in self.objects.as_mut_slice(){
-            loop {
-                let part_obj = obj.next(portion);
-                if let None = part_obj { break }
-                job.push(part_obj.unwrap());
-                let in_job: usize = job.iter().map(|el| el.polygons.len()).sum();
-                if in_job >= portion {
-                    jobs.push(job);
-                    job = Vec::new();
-                }
+        let (center,radius)=self.bounds();
+        let mut maps=Vec::new();
+        for light in self.context.lights.iter(){
+            if !light.casts_shadow(){
+                maps.push(None);
+                continue
             }
+            let matrix=self.context.view_port*shadow::light_matrix(light,&center,radius);
+            let transforms=self.objects.iter()
+                .map(|obj| Transforms::new(&matrix,obj.model_matrix()))
+                .collect::<Vec<Transforms>>();
+            let draws=draw_calls(&self.objects,Some(&DepthShader),&transforms,&[],&[],center,Vector::new(0.,0.,0.));
+            let screen=Screen{height:settings.size,width:settings.size,sight:None};
+            geometry(&self.objects,&draws,screen,&self.pool,&mut self.buffers);
+            let mut depth=TGAImage::new(settings.size,settings.size);
+            raster::rasterize(&mut depth,&self.buffers.triangles,&draws,&self.pool,&mut self.buffers.bins);
+            maps.push(Some(ShadowMap::new(&settings,matrix,depth.depth_buffer())));
         }
-        jobs.push(job);
-        let mut handles =Vec::new();
-
-        for job in jobs{
-            let (height,width)=(self.image.height,self.image.width);
-            let image=self.image.clone();
-            let context=self.context.clone();
-
-            let handle=thread::spawn(move|| {
-                let now=SystemTime::now();
-                for mut obj in job {
-
-                        let mut mod_matrix=context.view_port.multiply(&context.projection)
-                            .multiply(&look_at(context.eye,obj.position,context.up));
-                        if let Some(mat)=&obj.mod_matrix{
-                            mod_matrix=mod_matrix.multiply(mat);
-                        }
-
-                        for poly in obj.polygons.as_mut_slice(){
-                            for point in poly.coords.as_mut_slice(){
-                                *point=mod_matrix.multiply(&point.to_matrix())
-                                    .to_vector()
-                                    .to_plane(height,width);
-                            }
-                        }
-
-                    obj.draw_self(image.clone(),context.light,context.visible);
-                }
-                let t=now.elapsed().unwrap().as_nanos();
-                println!("thread time {}",t);
-            });
-            handles.push(handle);
+        maps
+    }
+
+    //the last drawn frame
+    pub fn image(&self)->&TGAImage{
+        &self.image
+    }
+
+    //renders a frame into image(), the stats say what it cost
+    pub fn draw(& mut self)->RenderStats{
+        let start=Instant::now();
+        self.context.shadow_maps=Arc::new(self.render_shadow_maps());
+        let shadows=start.elapsed();
+
+        let (height,width)=(self.image.height,self.image.width);
+        let view_proj=self.context.view_proj();
+        let transforms=self.objects.iter()
+            .map(|obj| Transforms::new(&view_proj,obj.model_matrix()))
+            .collect::<Vec<Transforms>>();
+        let context=&self.context;
+        let draws=draw_calls(&self.objects,None,&transforms,&context.lights,&context.shadow_maps,context.camera.position
+                             ,context.ambient);
+        let screen=Screen{height,width,sight:Some(context.visible)};
+        let (geometry_jobs,triangles)=geometry(&self.objects,&draws,screen,&self.pool,&mut self.buffers);
+        let geometry_done=start.elapsed();
+
+        //fragments split by screen bands
+        self.image.clear();
+        let fragments=raster::rasterize(&mut self.image,&self.buffers.triangles,&draws,&self.pool,&mut self.buffers.bins);
+        let raster_done=start.elapsed();
+
+        if let Some(settings)=&self.ssao{
+            self.image.apply_occlusion(&self.ambient_occlusion(settings));
         }
-        for handle in handles{
-            handle.join().unwrap();
+        let frame=start.elapsed();
+        RenderStats{threads:self.pool.threads(),frame,shadows,geometry:geometry_done-shadows,raster:raster_done-geometry_done
+            ,post:frame-raster_done,geometry_jobs,triangles,fragments}
+    }
+}
+
+//the plane triangles are projected on, sight is the direction back faces are culled
+//against, None keeps both sides
+#[derive(Copy,Clone)]
+struct Screen{
+    height:usize,
+    width:usize,
+    sight:Option<Vector<f32>>,
+}
+
+//storage kept from frame to frame so drawing does not allocate it again
+#[derive(Default)]
+struct Buffers{
+    jobs:Vec<JobBuffers>,
+    triangles:Vec<ScreenTriangle>,
+    //triangles per screen band
+    bins:Vec<Vec<usize>>,
+}
+
+#[derive(Default)]
+struct JobBuffers{
+    triangles:Vec<ScreenTriangle>,
+    cache:VertexCache,
+    stats:TriangleStats,
+}
+
+//vertex processing, clipping and culling split by triangles, buffers.triangles ends up in
+//object and triangle order whatever the number of threads; returns the time of every job
+//and what happened to the triangles
+fn geometry(objects:&[Object],draws:&[DrawCall],screen:Screen,pool:&ThreadPool,buffers:&mut Buffers)
+    ->(Vec<Duration>,TriangleStats){
+    let total=objects.iter().map(|obj| obj.mesh.len()).sum::<usize>();
+    let portion=(total/pool.threads()).max(MIN_ON_THREAD);
+    let mut jobs=vec![Vec::new()];
+    let mut in_job=0;
+    for (index,obj) in objects.iter().enumerate(){
+        let mut start=0;
+        while start<obj.mesh.len(){
+            let end=(start+portion-in_job).min(obj.mesh.len());
+            jobs.last_mut().unwrap().push((index,start..end));
+            in_job+=end-start;
+            start=end;
+            if in_job>=portion{
+                jobs.push(Vec::new());
+                in_job=0;
+            }
         }
-        //self.image.flip_vertically();
-        //self.image.write_tga_file(FILE_OUTPUT_PATH);
-        &self.image
     }
+    jobs.retain(|job| !job.is_empty());
+
+    let bases=draw_bases(objects);
+    let bases=&bases;
+    let screen=&screen;
+    buffers.jobs.resize_with(jobs.len(),JobBuffers::default);
+    let mut times=vec![Duration::default();jobs.len()];
+    let work=jobs.into_iter().zip(buffers.jobs.iter_mut()).zip(times.iter_mut())
+        .map(|((job,buffers),time)| Box::new(move ||{
+            let now=Instant::now();
+            buffers.triangles.clear();
+            buffers.stats=TriangleStats::default();
+            for (index,range) in job{
+                objects[index].triangles(range,draws,bases[index],screen,buffers);
+            }
+            *time=now.elapsed();
+        }) as Box<dyn FnOnce()+Send>)
+        .collect();
+    pool.run(work);
+
+    buffers.triangles.clear();
+    let mut stats=TriangleStats::default();
+    for job in buffers.jobs.iter_mut().take(times.len()){
+        buffers.triangles.append(&mut job.triangles);
+        stats+=job.stats;
+    }
+    (times,stats)
 }
 
-fn look_at(eye:Vector<f32>,center:Vector<f32>,up:Vector<f32>)->Matrix{
-    let z_vec=(eye-center).normalize();
-    let x=up.vector_prod(z_vec);
-    let y=z_vec.vector_prod(x);
-    let (mut camera_view,mut trans_matrix)=(Matrix::ident(4),Matrix::ident(4));
-    for i in 0..3{
-        camera_view[0][i]=x[i];
-        camera_view[1][i]=y[i];
-        camera_view[2][i]=z_vec[i];
-        trans_matrix[i][3]=-center[i];
-    }
-    camera_view.multiply(&trans_matrix)
-}
\ No newline at end of file
+fn load_texture(textures:&mut HashMap<PathBuf,Arc<Texture>>,dir:&Path,name:&Option<String>,color_format:Colors)
+    ->Result<Option<Arc<Texture>>,ObjError>{
+    let path=match name {
+        Some(name)=>dir.join(name),
+        None=>return Ok(None),
+    };
+    if let Some(texture)=textures.get(&path){
+        return Ok(Some(texture.clone()))
+    }
+    let texture=file_input::read_texture_file(&path.to_string_lossy(),color_format)
+        .map_err(|error| ObjError::Texture{path:path.to_string_lossy().into_owned(),error})?;
+    textures.insert(path,texture.clone());
+    Ok(Some(texture))
+}
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::fs;
+
+    //writes text next to the other test files in the temp dir and returns the path
+    fn temp_file(name:&str,text:&str)->String{
+        let path=std::env::temp_dir().join(format!("simpleOpenGL-{}-{}",std::process::id(),name));
+        fs::write(&path,text).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn triangle(directives:&str)->String{
+        format!("{}v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n",directives)
+    }
+
+    #[test]
+    fn missing_mtllib_falls_back_to_the_default_material(){
+        let path=temp_file("nolib.obj",&triangle("mtllib absent.mtl\nusemtl red\n"));
+        let obj=Object::new(Vector::new(0.,0.,0.)).build(&path).unwrap();
+        assert_eq!(obj.materials.len(),1);
+        assert_eq!(obj.materials[0].name,"");
+    }
+
+    #[test]
+    fn undefined_usemtl_falls_back_to_the_default_material(){
+        let lib=temp_file("defined.mtl","newmtl red\nKd 1 0 0\n");
+        let lib=Path::new(&lib).file_name().unwrap().to_string_lossy().into_owned();
+        let path=temp_file("undefined.obj",&triangle(&format!("mtllib {}\nusemtl red\nusemtl blue\n",lib)));
+        let obj=Object::new(Vector::new(0.,0.,0.)).build(&path).unwrap();
+        let names:Vec<&str>=obj.materials.iter().map(|material| material.name.as_str()).collect();
+        assert_eq!(names,["red",""]);
+    }
+
+    #[test]
+    fn malformed_mtllib_is_still_an_error(){
+        let lib=temp_file("malformed.mtl","newmtl red\nKd 1 zero 0\n");
+        let lib=Path::new(&lib).file_name().unwrap().to_string_lossy().into_owned();
+        let path=temp_file("malformed.obj",&triangle(&format!("mtllib {}\nusemtl red\n",lib)));
+        match Object::new(Vector::new(0.,0.,0.)).build(&path) {
+            Err(ObjError::Material{error,..})=>assert!(matches!(*error,ObjError::Parse{line:2,..})),
+            _=>panic!("expected a material error"),
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::colors::Colors;
+use crate::tga;
+use crate::tga::Tga;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum ImageFormat{
+    //run length encoded
+    Tga,
+    Png,
+    //binary P6
+    Ppm,
+    //plain text P3, one pixel per line
+    PpmAscii,
+}
+
+impl ImageFormat{
+    //tga, png, ppm or ppm-ascii
+    pub fn from_name(name:&str)->Option<ImageFormat>{
+        match name.to_ascii_lowercase().as_str() {
+            "tga"=>Some(ImageFormat::Tga),
+            "png"=>Some(ImageFormat::Png),
+            "ppm"=>Some(ImageFormat::Ppm),
+            "ppm-ascii"=>Some(ImageFormat::PpmAscii),
+            _=>None,
+        }
+    }
+
+    //.tga, .png and .ppm, the binary flavour of ppm is picked
+    pub fn from_path(path:&str)->Option<ImageFormat>{
+        let ext=Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "tga"=>Some(ImageFormat::Tga),
+            "png"=>Some(ImageFormat::Png),
+            "ppm"=>Some(ImageFormat::Ppm),
+            _=>None,
+        }
+    }
+}
+
+//image rows run top to bottom, ppm drops the alpha channel
+pub fn write<W:Write>(mut writer:W,image:&Tga,format:ImageFormat)->io::Result<()>{
+    match format {
+        ImageFormat::Tga=>tga::write(writer,image,true),
+        ImageFormat::Png=>write_png(&mut writer,image),
+        ImageFormat::Ppm | ImageFormat::PpmAscii=>write_ppm(&mut writer,image,format==ImageFormat::PpmAscii),
+    }
+}
+
+fn write_png<W:Write>(writer:&mut W,image:&Tga)->io::Result<()>{
+    let format=match image.color_type {
+        Colors::RGBA=>imagefmt::ColFmt::RGBA,
+        Colors::RGB=>imagefmt::ColFmt::RGB,
+        Colors::Gray=>imagefmt::ColFmt::Y,
+        Colors::RGBAFloat=>return Err(io::Error::new(io::ErrorKind::InvalidInput,"png stores 8 bits per channel")),
+    };
+    match imagefmt::png::write(writer,image.width,image.height,format,&image.data,imagefmt::ColType::Auto,None) {
+        Ok(())=>Ok(()),
+        Err(imagefmt::Error::Io(e))=>Err(e),
+        Err(e)=>Err(io::Error::new(io::ErrorKind::InvalidInput,e.to_string())),
+    }
+}
+
+fn write_ppm<W:Write>(writer:&mut W,image:&Tga,ascii:bool)->io::Result<()>{
+    if image.color_type==Colors::RGBAFloat{
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,"ppm stores 8 bits per channel"))
+    }
+    let bytes=image.color_type.bytes();
+    let rgb=|pixel:&[u8]| match bytes {
+        1=>[pixel[0],pixel[0],pixel[0]],
+        _=>[pixel[0],pixel[1],pixel[2]],
+    };
+    let pixels=image.data.chunks_exact(bytes).map(rgb);
+    if ascii{
+        write!(writer,"P3\n{} {}\n255\n",image.width,image.height)?;
+        for [r,g,b] in pixels{
+            writeln!(writer,"{} {} {}",r,g,b)?;
+        }
+    }else{
+        write!(writer,"P6\n{} {}\n255\n",image.width,image.height)?;
+        writer.write_all(&pixels.flatten().collect::<Vec<u8>>())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //2x2 rgba whose alpha differs from every color channel
+    fn image()->Tga{
+        let data=(0..16).map(|i| if i%4==3{200}else{i*10}).collect();
+        Tga{width:2,height:2,color_type:Colors::RGBA,data}
+    }
+
+    fn encode(image:&Tga,format:ImageFormat)->Vec<u8>{
+        let mut file=Vec::new();
+        write(&mut file,image,format).unwrap();
+        file
+    }
+
+    #[test]
+    fn binary_ppm_drops_alpha(){
+        let file=encode(&image(),ImageFormat::Ppm);
+        let header=b"P6\n2 2\n255\n";
+        assert_eq!(&file[..header.len()],header);
+        assert_eq!(file.len(),header.len()+2*2*3);
+        assert_eq!(&file[header.len()..],[0,10,20,40,50,60,80,90,100,120,130,140]);
+    }
+
+    #[test]
+    fn ascii_ppm_has_a_pixel_per_line(){
+        let file=String::from_utf8(encode(&image(),ImageFormat::PpmAscii)).unwrap();
+        let lines:Vec<&str>=file.lines().collect();
+        assert_eq!(lines,["P3","2 2","255","0 10 20","40 50 60","80 90 100","120 130 140"]);
+    }
+
+    #[test]
+    fn one_pixel_round_trips_through_every_format(){
+        let pixel=Tga{width:1,height:1,color_type:Colors::RGBA,data:vec![12,34,56,78]};
+        let tga=tga::read(&encode(&pixel,ImageFormat::Tga)[..]).unwrap();
+        assert_eq!((tga.width,tga.height,tga.color_type,tga.data),(1,1,Colors::RGBA,vec![12,34,56,78]));
+        let png=imagefmt::png::read::<_,u8>(&mut io::Cursor::new(encode(&pixel,ImageFormat::Png)),imagefmt::ColFmt::RGBA).unwrap();
+        assert_eq!((png.w,png.h,png.buf),(1,1,vec![12,34,56,78]));
+        assert_eq!(encode(&pixel,ImageFormat::Ppm),b"P6\n1 1\n255\n\x0c\x22\x38");
+        assert_eq!(encode(&pixel,ImageFormat::PpmAscii),b"P3\n1 1\n255\n12 34 56\n");
+    }
+
+    #[test]
+    fn names_and_extensions(){
+        assert_eq!(ImageFormat::from_name("PPM-ascii"),Some(ImageFormat::PpmAscii));
+        assert_eq!(ImageFormat::from_path("out/frame.PNG"),Some(ImageFormat::Png));
+        for path in ["frame.jpg","frame","-"]{
+            assert_eq!(ImageFormat::from_path(path),None);
+        }
+    }
+}
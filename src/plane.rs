@@ -1,68 +1,128 @@
-use imagefmt::ColFmt;
-use imagefmt::Error;
-use imagefmt::ColType;
 use std::f32;
 use crate::dimensional::Vector;
-use crate::texture::Texture;
+use crate::shader::Shader;
+use crate::shader::Uniforms;
+use crate::shader::Varyings;
+use crate::shader::Fragment;
 use num::NumCast;
-use std::sync::Arc;
-use std::sync::Mutex;
 use crate::colors::TGAColor;
+use crate::colors::Colors;
+use crate::tga::Tga;
+use crate::output;
+use crate::output::ImageFormat;
+use crate::stats::FragmentStats;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::io::Write;
 
 
 pub struct TGAImage{
     pub height:usize,
     pub width:usize,
-    pixels:Mutex<Vec<TGAColor>>,
-    z_buff:Mutex<Vec<f32>>
+    pixels:Vec<TGAColor>,
+    z_buff:Vec<f32>
+}
+
+//whole rows of the image borrowed in place, owned by a single worker while rasterizing
+pub struct Band<'a>{
+    //first row of the band
+    pub y:usize,
+    pub height:usize,
+    pub width:usize,
+    pixels:&'a mut [TGAColor],
+    z_buff:&'a mut [f32],
+    //what filling the band cost so far
+    pub fragments:FragmentStats,
 }
 
 
 impl TGAImage {
     pub fn new(height: usize, width: usize) -> TGAImage {
-        let pixels =Mutex::new( vec![TGAColor::new(255, 0, 255, 255); height * width]);
-        let z_buff = Mutex::new(vec![f32::MIN; height * width]);
+        let pixels = vec![TGAColor::new(255, 0, 255, 255); height * width];
+        let z_buff = vec![f32::MIN; height * width];
         TGAImage { height, width, pixels, z_buff }
     }
 
-    pub fn set_pixel(&self, point: Vector<f32>, pixel: TGAColor) -> Result<(), String> {
+    //back to the state of new, keeps the buffers
+    pub fn clear(&mut self){
+        self.pixels.iter_mut().for_each(|pixel| *pixel=TGAColor::new(255, 0, 255, 255));
+        self.z_buff.iter_mut().for_each(|z| *z=f32::MIN);
+    }
+
+    pub fn set_pixel(&mut self, point: Vector<f32>, pixel: TGAColor) -> Result<(), String> {
         if let Err(e) = self.check_boundaries(&point) { return Err(e) }
         //TODO lifetime reference
         self.set_pixel_unchecked(&point, pixel);
         Ok(())
     }
 
-    fn set_pixel_unchecked(&self, vec:&Vector<f32>, pixel: TGAColor) {
+    fn set_pixel_unchecked(&mut self, vec:&Vector<f32>, pixel: TGAColor) {
         let index = vec.y as usize * self.width + vec.x as usize;
-        let mut z_buff=self.z_buff.lock().unwrap();
-        let mut pixels=self.pixels.lock().unwrap();
-        if z_buff[index] < vec.z {
-            pixels[index] = pixel;
-            z_buff[index] = vec.z;
+        if self.z_buff[index] < vec.z {
+            self.pixels[index] = pixel;
+            self.z_buff[index] = vec.z;
         }
     }
 
-    fn set_pixels(&self,tulp:Vec<(Vector<f32>, TGAColor)>){
-        let mut z_buff=self.z_buff.lock().unwrap();
-        let mut pixels=self.pixels.lock().unwrap();
-        for (point,pixel) in tulp{
-            if point.x<0.||point.y<0.{return;}
-            let index = point.y as usize * self.width + point.x as usize;
-            if index>pixels.len(){continue}
-            if z_buff[index] < point.z {
-                pixels[index] = pixel;
-                z_buff[index] = point.z;
-            }
+    pub fn as_vec(&self) -> Vec<u8> {
+        TGAColor::from_arr_to_arr(self.pixels.as_slice())
+    }
+
+    //greatest z per pixel in plane coordinates, f32::MIN where nothing was drawn
+    pub fn depth_buffer(&self)->Vec<f32>{
+        self.z_buff.clone()
+    }
+
+    //replaces every pixel, f gets the row-major index and the current color
+    pub fn map_pixels<F:Fn(usize,&TGAColor)->TGAColor>(&mut self,f:F){
+        for (index,pixel) in self.pixels.iter_mut().enumerate(){
+            *pixel=f(index,pixel);
         }
     }
 
-    pub fn as_vec(&self) -> Vec<u8> {
-        TGAColor::from_arr_to_arr(self.pixels.lock().unwrap().as_slice())
+    //splits the image into bands of rows from the bottom up, the last one is cut at the
+    //image border; nothing is copied, the bands write straight into the image
+    pub fn bands(&mut self,rows:usize)->impl Iterator<Item=Band<'_>>{
+        let width=self.width;
+        let len=(rows*width).max(1);
+        self.pixels.chunks_mut(len).zip(self.z_buff.chunks_mut(len)).enumerate()
+            .map(move |(index,(pixels,z_buff))| Band{y:index*rows,height:pixels.len()/width,width,pixels,z_buff
+                ,fragments:FragmentStats::default()})
+    }
+
+    //scales the color of every pixel by its ambient visibility
+    pub fn apply_occlusion(&mut self,occlusion:&[f32]){
+        self.map_pixels(|index,pixel| pixel.with_rgb(&(pixel.to_rgb()*occlusion[index])));
+    }
+
+    //row 0 of the image is the bottom, the tga rows run top to bottom
+    pub fn to_tga(&self)->Tga{
+        let data=self.as_vec().chunks_exact(self.width*4).rev().flatten().cloned().collect();
+        Tga{width:self.width,height:self.height,color_type:Colors::RGBA,data}
+    }
+
+    //run length encoded 32 bit
+    pub fn write_tga_file(&self, path: &str) -> io::Result<()> {
+        self.write_file_as(path,ImageFormat::Tga)
+    }
+
+    //the format follows the extension of path, see ImageFormat::from_path
+    pub fn write_file(&self,path:&str)->io::Result<()>{
+        let format=ImageFormat::from_path(path).ok_or_else(||
+            io::Error::new(io::ErrorKind::InvalidInput,"unknown image extension, expected tga, png or ppm"))?;
+        self.write_file_as(path,format)
     }
 
-    pub fn write_tga_file(&self, path: &str) -> Result<(), Error> {
-        imagefmt::write(path, self.width, self.height
-                        , ColFmt::RGBA, self.as_vec().as_slice(), ColType::Auto)
+    pub fn write_file_as(&self,path:&str,format:ImageFormat)->io::Result<()>{
+        let mut writer=BufWriter::new(File::create(path)?);
+        self.write(&mut writer,format)?;
+        writer.flush()
+    }
+
+    //streams the encoded image, e.g. to stdout
+    pub fn write<W:Write>(&self,writer:W,format:ImageFormat)->io::Result<()>{
+        output::write(writer,&self.to_tga(),format)
     }
 
     pub fn draw_line(&mut self, start: &Vector<usize>, end: &Vector<usize>, color: &TGAColor) -> Result<(), String> {
@@ -101,79 +161,67 @@ impl TGAImage {
         Ok(())
     }
 
-    pub fn fill_triangle(&self, light:&Vector<f32>, coords: &mut [Vector<f32>],text_coords:&mut [Vector<f32>]
-    ,texture:&Option<Arc<Texture>>,norm_coords:&mut [Vector<f32>],norm_map:&Option<Arc<Texture>>,sp_map:&Option<Arc<Texture>>) {
-
-        if coords[0].y==coords[1].y && coords[0].y==coords[2].y{return;}
-        if coords[0].y>coords[1].y{coords.swap(0,1); text_coords.swap(0,1); norm_coords.swap(0,1);}
-        if coords[0].y>coords[2].y{coords.swap(0,2); text_coords.swap(0,2); norm_coords.swap(0,2);}
-        if coords[1].y>coords[2].y{coords.swap(1,2); text_coords.swap(1,2); norm_coords.swap(1,2);}
-
-        let mut pixels=Vec::new();
-
-        let (mut uvA,mut uvB)=(text_coords[0],text_coords[0]);
-        let (mut A,mut B)=(coords[0],coords[0]);
-        let (mut unA,mut unB)=(norm_coords[0],norm_coords[0]);
-
-        let tg1_text=(text_coords[2]-text_coords[0]) / (coords[2].y-coords[0].y);
-        let mut tg2_text=(text_coords[1]-text_coords[0])/ (coords[1].y-coords[0].y);
-        let tg1_norm=(norm_coords[2]-norm_coords[0]) / (coords[2].y-coords[0].y);
-        let mut tg2_norm=(norm_coords[1]-norm_coords[0])/ (coords[1].y-coords[0].y);
-        let tg_last=(coords[2]-coords[0])/(coords[2].y-coords[0].y);
-        let mut tg_middle=(coords[1]-coords[0])/(coords[1].y-coords[0].y);
-
-        for dy in coords[0].y as usize..coords[2].y as usize{
-            if coords[1].y as usize==dy{
-                uvB=text_coords[1];
-                tg2_text=(text_coords[2]-text_coords[1])/ (coords[2].y-coords[1].y);
-                unB=norm_coords[1];
-                tg2_norm=(norm_coords[2]-norm_coords[1])/ (coords[2].y-coords[1].y);
-                B=coords[1];
-                tg_middle=(coords[2]-coords[1])/(coords[2].y-coords[1].y);
-            }
-
-            let (start,end)=if A.x<B.x{(A,B)}
-                else{ (B,A)};
-
-            for dx in start.x as usize..end.x as usize{
-                let mut phi=(dx as f32-A.x)/(B.x-A.x);
-                if phi>1.{phi=1.};
-                if phi<0.{phi=0.};
-                let P=A+(B-A)*phi;
-                let uvP=uvA+(uvB-uvA)*phi;
-                let unP=unA+(unB-unA)*phi;
-
-                let mut pixel= match texture {
-                    Some(val)=>val.get_pixel(uvP.x as usize,uvP.y as usize),
-                    None=> TGAColor::new(0,0,0,255),
-                };
-                let mut intensity=0.;
-                if let Some(val)=norm_map{
-                    let norm_pixel=val.get_pixel(unP.x as usize,unP.y as usize).to_vector().normalize();
-                    intensity+=norm_pixel.scalar_prod(light);
-                };
-                if let Some(val)=sp_map{
-                    intensity+=val.get_pixel_grey(unP.x as usize,unP.y as usize)*0.6;
-                };
-                pixel.add_intensity(intensity);
-                pixels.push((P,pixel));
-            }
-            unA=unA+tg1_norm;
-            unB=unB+tg2_norm;
-            uvA=uvA+tg1_text;
-            uvB=uvB+tg2_text;
-            A=A+tg_last;
-            B=B+tg_middle;
+    pub fn flip_vertically(&mut self) {
+        let mut top_half = Vec::new();
+        for y in 0..self.height {
+            top_half.append(&mut self.pixels[self.width * (self.height - y - 1)..self.width * (self.height - y)].to_vec())
         }
-        self.set_pixels(pixels);
+        self.pixels.swap_with_slice(&mut top_half);
     }
+}
 
-    pub fn flip_vertically(&self) {
-        let mut top_half = Vec::new();
-        let mut pixels=self.pixels.lock().unwrap();
-        for y in 0..self.height {
-            top_half.append(&mut pixels[self.width * (self.height - y - 1)..self.width * (self.height - y)].to_vec())
+impl Band<'_>{
+    //w holds the clip-space w of every vertex, varyings are interpolated perspective-correct
+    pub fn fill_triangle(&mut self, coords: &[Vector<f32>;3],w:&[f32;3],varyings:&[Varyings;3]
+    ,shader:&dyn Shader,uniforms:&Uniforms) {
+
+        let area=edge(&coords[0],&coords[1],coords[2].x,coords[2].y);
+        if area==0.{return;}
+        let inv_w=[1./w[0],1./w[1],1./w[2]];
+        //change of every barycentric one pixel along x and y
+        let step_x=[(coords[1].y-coords[2].y)/area,(coords[2].y-coords[0].y)/area,(coords[0].y-coords[1].y)/area];
+        let step_y=[(coords[2].x-coords[1].x)/area,(coords[0].x-coords[2].x)/area,(coords[1].x-coords[0].x)/area];
+
+        let (min_x,min_y,max_x,max_y)=bounds(coords,self.y+self.height,self.width);
+        let min_y=min_y.max(self.y);
+
+        for y in min_y..max_y{
+            for x in min_x..max_x{
+                let (px,py)=(x as f32+0.5,y as f32+0.5);
+                let bar=[edge(&coords[1],&coords[2],px,py)/area,
+                    edge(&coords[2],&coords[0],px,py)/area,
+                    edge(&coords[0],&coords[1],px,py)/area];
+                if bar[0]<0.||bar[1]<0.||bar[2]<0.{continue}
+
+                //screen depth is affine in screen space, everything else is divided by w
+                let z=coords[0].z*bar[0]+coords[1].z*bar[1]+coords[2].z*bar[2];
+                //the band is ours alone, so hidden pixels are rejected before shading
+                let index=(y-self.y)*self.width+x;
+                if self.z_buff[index]>=z{
+                    self.fragments.depth_rejected+=1;
+                    continue
+                }
+                self.fragments.shaded+=1;
+                let fragment=Fragment::new(x,y,varyings,bar,inv_w,step_x,step_y);
+                if let Some(pixel)=shader.fragment(uniforms,&fragment){
+                    self.pixels[index]=pixel;
+                    self.z_buff[index]=z;
+                }
+            }
         }
-        pixels.swap_with_slice(&mut top_half);
     }
-}
\ No newline at end of file
+}
+
+//pixel rectangle [min,max) a triangle can cover, cut at height and width
+pub fn bounds(coords:&[Vector<f32>;3],height:usize,width:usize)->(usize,usize,usize,usize){
+    let min_x=coords.iter().map(|c| c.x).fold(f32::MAX,f32::min).max(0.) as usize;
+    let min_y=coords.iter().map(|c| c.y).fold(f32::MAX,f32::min).max(0.) as usize;
+    let max_x=(coords.iter().map(|c| c.x).fold(f32::MIN,f32::max).ceil().max(0.) as usize).min(width);
+    let max_y=(coords.iter().map(|c| c.y).fold(f32::MIN,f32::max).ceil().max(0.) as usize).min(height);
+    (min_x,min_y,max_x,max_y)
+}
+
+//twice the signed area of (a,b,p)
+fn edge(a:&Vector<f32>,b:&Vector<f32>,px:f32,py:f32)->f32{
+    (b.x-a.x)*(py-a.y)-(b.y-a.y)*(px-a.x)
+}
@@ -0,0 +1,153 @@
+use std::mem;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::thread::JoinHandle;
+
+type Job=Box<dyn FnOnce()+Send+'static>;
+
+//workers live as long as the pool, with a single thread jobs run on the caller in order
+pub struct ThreadPool{
+    threads:usize,
+    sender:Option<Sender<Job>>,
+    workers:Vec<JoinHandle<()>>,
+}
+
+impl ThreadPool{
+    pub fn new(threads:usize)->ThreadPool{
+        let threads=threads.max(1);
+        if threads==1{
+            return ThreadPool{threads,sender:None,workers:Vec::new()}
+        }
+        let (sender,receiver)=mpsc::channel::<Job>();
+        let receiver=Arc::new(Mutex::new(receiver));
+        let workers=(0..threads).map(|_|{
+            let receiver=receiver.clone();
+            thread::spawn(move || work(&receiver))
+        }).collect();
+        ThreadPool{threads,sender:Some(sender),workers}
+    }
+
+    pub fn threads(&self)->usize{
+        self.threads
+    }
+
+    //returns once every job has finished, so jobs may borrow from the caller,
+    //a panicking job is reported after the others are done
+    pub fn run<'a>(&self,jobs:Vec<Box<dyn FnOnce()+Send+'a>>){
+        let sender=match &self.sender {
+            Some(sender)=>sender,
+            None=>{
+                for job in jobs{ job() }
+                return
+            },
+        };
+        let count=jobs.len();
+        let (done,finished)=mpsc::channel();
+        for job in jobs{
+            let done=done.clone();
+            let job:Box<dyn FnOnce()+Send+'a>=Box::new(move ||{
+                let result=panic::catch_unwind(AssertUnwindSafe(job));
+                let _=done.send(result.is_ok());
+            });
+            // SAFETY: the queue needs 'static jobs but these borrow for 'a, so run must neither
+            // return nor unwind while one is queued or running. Nothing between here and the wait
+            // below can panic: a send that fails hands the job back and it runs right here. The
+            // wait takes one report per job and cannot end early because `done` is still alive,
+            // every wrapper reports exactly once since it catches the panic of its job, and the
+            // only panic of run comes after the wait.
+            let job=unsafe{ mem::transmute::<Box<dyn FnOnce()+Send+'a>,Job>(job) };
+            if let Err(mpsc::SendError(job))=sender.send(job){
+                job();
+            }
+        }
+        let succeeded=finished.iter().take(count).filter(|ok| *ok).count();
+        if succeeded<count{
+            panic!("{} of {} render jobs panicked",count-succeeded,count);
+        }
+    }
+}
+
+fn work(receiver:&Mutex<Receiver<Job>>){
+    loop {
+        //the lock is released before running the job
+        let job=receiver.lock().unwrap().recv();
+        match job {
+            Ok(job)=>job(),
+            Err(_)=>break,
+        }
+    }
+}
+
+impl Drop for ThreadPool{
+    fn drop(&mut self){
+        //closing the queue stops the workers
+        self.sender=None;
+        for worker in self.workers.drain(..){
+            let _=worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    fn jobs<'a>(count:usize,sum:&'a AtomicUsize,order:&'a Mutex<Vec<usize>>)->Vec<Box<dyn FnOnce()+Send+'a>>{
+        (0..count).map(|index| Box::new(move ||{
+            sum.fetch_add(index,Ordering::SeqCst);
+            order.lock().unwrap().push(index);
+        }) as Box<dyn FnOnce()+Send>).collect()
+    }
+
+    #[test]
+    fn one_thread_runs_inline_in_order(){
+        let pool=ThreadPool::new(1);
+        assert_eq!(pool.threads(),1);
+        let (sum,order)=(AtomicUsize::new(0),Mutex::new(Vec::new()));
+        pool.run(jobs(10,&sum,&order));
+        assert_eq!(sum.load(Ordering::SeqCst),45);
+        assert_eq!(*order.lock().unwrap(),(0..10).collect::<Vec<usize>>());
+        //zero threads still runs the jobs
+        assert_eq!(ThreadPool::new(0).threads(),1);
+    }
+
+    #[test]
+    fn workers_finish_every_job_before_returning(){
+        let pool=ThreadPool::new(3);
+        let (sum,order)=(AtomicUsize::new(0),Mutex::new(Vec::new()));
+        pool.run(jobs(100,&sum,&order));
+        assert_eq!(sum.load(Ordering::SeqCst),4950);
+        assert_eq!(order.lock().unwrap().len(),100);
+    }
+
+    #[test]
+    fn a_panicking_job_is_reported_after_the_others(){
+        let pool=ThreadPool::new(2);
+        let finished=AtomicUsize::new(0);
+        let result=panic::catch_unwind(AssertUnwindSafe(||{
+            let finished=&finished;
+            let mut work=(0..8).map(|_| Box::new(move ||{
+                thread::sleep(std::time::Duration::from_millis(5));
+                finished.fetch_add(1,Ordering::SeqCst);
+            }) as Box<dyn FnOnce()+Send>).collect::<Vec<_>>();
+            work.insert(3,Box::new(|| panic!("job failed")));
+            pool.run(work);
+        }));
+        let message=result.err().unwrap();
+        assert_eq!(message.downcast_ref::<String>().unwrap(),"1 of 9 render jobs panicked");
+        //every borrowing job was done before run unwound
+        assert_eq!(finished.load(Ordering::SeqCst),8);
+        //the workers survive the panic
+        let (sum,order)=(AtomicUsize::new(0),Mutex::new(Vec::new()));
+        pool.run(jobs(4,&sum,&order));
+        assert_eq!(sum.load(Ordering::SeqCst),6);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::dimensional::Vector;
+use crate::plane;
+use crate::plane::TGAImage;
+use crate::plane::Band;
+use crate::shader::Shader;
+use crate::shader::Uniforms;
+use crate::shader::Varyings;
+use crate::pool::ThreadPool;
+use crate::stats::FragmentStats;
+
+//rows of the screen bands handed to workers
+pub const BAND_ROWS:usize=16;
+
+//a clipped triangle in plane coordinates waiting to be filled
+pub struct ScreenTriangle{
+    pub coords:[Vector<f32>;3],
+    //clip-space w of every vertex
+    pub w:[f32;3],
+    pub varyings:[Varyings;3],
+    //index of the draw call that shades it
+    pub draw:usize,
+}
+
+//what the fragments of a group of triangles are shaded with
+pub struct DrawCall<'a>{
+    pub shader:&'a dyn Shader,
+    pub uniforms:Uniforms<'a>,
+}
+
+//indices of the triangles touching every band, in submission order so the depth
+//test resolves ties the same way whatever the number of threads; bins are kept
+//between calls so binning does not allocate once they are large enough
+fn bin(triangles:&[ScreenTriangle],height:usize,width:usize,bins:&mut Vec<Vec<usize>>)->usize{
+    let bands=height.div_ceil(BAND_ROWS);
+    if bins.len()<bands{
+        bins.resize_with(bands,Vec::new);
+    }
+    for bin in bins.iter_mut(){
+        bin.clear();
+    }
+    for (index,triangle) in triangles.iter().enumerate(){
+        let (min_x,min_y,max_x,max_y)=plane::bounds(&triangle.coords,height,width);
+        if min_x>=max_x || min_y>=max_y{ continue }
+        for bin in &mut bins[min_y/BAND_ROWS..=(max_y-1)/BAND_ROWS]{
+            bin.push(index);
+        }
+    }
+    bands
+}
+
+fn fill_band(band:&mut Band,bin:&[usize],triangles:&[ScreenTriangle],draws:&[DrawCall]){
+    for &index in bin{
+        let triangle=&triangles[index];
+        let draw=&draws[triangle.draw];
+        band.fill_triangle(&triangle.coords,&triangle.w,&triangle.varyings,draw.shader,&draw.uniforms);
+    }
+}
+
+//fills the triangles into image, every worker owns the bands it was dealt so the
+//color and depth buffers are never shared; returns the fragment counts of all bands
+pub fn rasterize(image:&mut TGAImage,triangles:&[ScreenTriangle],draws:&[DrawCall],pool:&ThreadPool
+                 ,bins:&mut Vec<Vec<usize>>)->FragmentStats{
+    let bands=bin(triangles,image.height,image.width,bins);
+    let mut work=(0..pool.threads()).map(|_| Vec::new()).collect::<Vec<Vec<(Band,&Vec<usize>)>>>();
+    //dealing the busy bands round robin keeps the crowded middle of the screen spread out
+    let busy=image.bands(BAND_ROWS).zip(&bins[..bands]).filter(|(_,bin)| !bin.is_empty());
+    for (index,job) in busy.enumerate(){
+        let workers=work.len();
+        work[index%workers].push(job);
+    }
+    let mut counts=vec![FragmentStats::default();work.len()];
+    let jobs=work.into_iter().zip(counts.iter_mut())
+        .filter(|(job,_)| !job.is_empty())
+        .map(|(job,fragments)| Box::new(move ||{
+            for (mut band,bin) in job{
+                fill_band(&mut band,bin,triangles,draws);
+                *fragments+=band.fragments;
+            }
+        }) as Box<dyn FnOnce()+Send>)
+        .collect();
+    pool.run(jobs);
+    let mut fragments=FragmentStats::default();
+    for count in counts{
+        fragments+=count;
+    }
+    fragments
+}
@@ -0,0 +1,264 @@
+use crate::dimensional::Vector;
+use crate::matrix::Matrix4;
+use crate::matrix::Vec4;
+use crate::material::Material;
+use crate::texture::Texture;
+use crate::texture::Filter;
+use crate::colors::TGAColor;
+use crate::light::Light;
+use crate::shadow::ShadowMap;
+
+pub const MAX_VARYINGS:usize=24;
+
+//fixed capacity so interpolating per fragment never allocates
+#[derive(Copy,Clone)]
+pub struct Varyings{
+    data:[f32;MAX_VARYINGS],
+    len:usize,
+}
+
+impl Varyings{
+    pub fn new()->Varyings{
+        Varyings{data:[0.;MAX_VARYINGS],len:0}
+    }
+    pub fn push(&mut self,val:f32)->&mut Self{
+        assert!(self.len<MAX_VARYINGS,"more than {} varyings",MAX_VARYINGS);
+        self.data[self.len]=val;
+        self.len+=1;
+        self
+    }
+    pub fn push_vector(&mut self,vector:&Vector<f32>)->&mut Self{
+        self.push(vector.x).push(vector.y).push(vector.z)
+    }
+    pub fn get(&self,index:usize)->f32{
+        self.data[index]
+    }
+    //three consecutive values starting at index
+    pub fn vector(&self,index:usize)->Vector<f32>{
+        Vector::new(self.data[index],self.data[index+1],self.data[index+2])
+    }
+    pub fn len(&self)->usize{
+        self.len
+    }
+    pub fn is_empty(&self)->bool{
+        self.len==0
+    }
+    pub fn lerp(&self,other:&Varyings,t:f32)->Varyings{
+        let mut res=*self;
+        for i in 0..self.len{
+            res.data[i]+=(other.data[i]-self.data[i])*t;
+        }
+        res
+    }
+    pub fn weighted(varyings:&[Varyings;3],weights:&[f32;3])->Varyings{
+        let mut res=Varyings::new();
+        res.len=varyings[0].len;
+        for i in 0..res.len{
+            res.data[i]=varyings[0].data[i]*weights[0]+varyings[1].data[i]*weights[1]+varyings[2].data[i]*weights[2];
+        }
+        res
+    }
+}
+
+impl Default for Varyings{
+    fn default()->Self{
+        Varyings::new()
+    }
+}
+
+pub struct Uniforms<'a>{
+    pub mvp:&'a Matrix4,
+    //object to world space, lights and the eye live in world space
+    pub model:&'a Matrix4,
+    //inverse-transpose of model, takes object normals to world space
+    pub normal_matrix:&'a Matrix4,
+    pub lights:&'a [Light],
+    //indexed like lights, None when the light casts no shadow
+    pub shadows:&'a [Option<ShadowMap>],
+    pub eye:Vector<f32>,
+    //ambient light of the scene, what reaches the surface is this times material.ambient
+    pub ambient:Vector<f32>,
+    pub material:&'a Material,
+}
+
+//object-space attributes of a single vertex, text_coord is the normalized uv
+pub struct Vertex{
+    pub position:Vector<f32>,
+    pub text_coord:Vector<f32>,
+    pub normal:Vector<f32>,
+}
+
+//a pixel being shaded, the screen space derivatives of its varyings are only
+//interpolated when asked for
+pub struct Fragment<'a>{
+    pub x:usize,
+    pub y:usize,
+    pub varyings:Varyings,
+    triangle:&'a [Varyings;3],
+    bar:[f32;3],
+    inv_w:[f32;3],
+    step_x:[f32;3],
+    step_y:[f32;3],
+}
+
+impl<'a> Fragment<'a>{
+    //bar are the screen barycentrics of the pixel, step_x and step_y their change per pixel
+    pub(crate) fn new(x:usize,y:usize,triangle:&'a [Varyings;3],bar:[f32;3],inv_w:[f32;3]
+                      ,step_x:[f32;3],step_y:[f32;3])->Fragment<'a>{
+        let varyings=Varyings::weighted(triangle,&perspective(&bar,&inv_w));
+        Fragment{x,y,varyings,triangle,bar,inv_w,step_x,step_y}
+    }
+    //change of the varyings one pixel to the right
+    pub fn dx(&self)->Varyings{
+        self.derivative(&self.step_x)
+    }
+    //change of the varyings one pixel up
+    pub fn dy(&self)->Varyings{
+        self.derivative(&self.step_y)
+    }
+    fn derivative(&self,step:&[f32;3])->Varyings{
+        let bar=[self.bar[0]+step[0],self.bar[1]+step[1],self.bar[2]+step[2]];
+        let mut res=Varyings::weighted(self.triangle,&perspective(&bar,&self.inv_w));
+        for i in 0..res.len{
+            res.data[i]-=self.varyings.data[i];
+        }
+        res
+    }
+}
+
+//screen barycentrics to perspective-correct weights, inv_w is 1/w of every vertex
+pub(crate) fn perspective(bar:&[f32;3],inv_w:&[f32;3])->[f32;3]{
+    let mut persp=[bar[0]*inv_w[0],bar[1]*inv_w[1],bar[2]*inv_w[2]];
+    let sum=persp[0]+persp[1]+persp[2];
+    for weight in persp.iter_mut(){ *weight/=sum; }
+    persp
+}
+
+pub trait Shader:Send+Sync{
+    //returns the clip-space position and the values to interpolate across the triangle
+    fn vertex(&self,uniforms:&Uniforms,vertex:&Vertex)->(Vec4,Varyings);
+    //None discards the fragment
+    fn fragment(&self,uniforms:&Uniforms,fragment:&Fragment)->Option<TGAColor>;
+}
+
+#[derive(Copy,Clone)]
+pub enum Shading{
+    Gouraud,
+    Phong,
+}
+
+//Blinn-Phong: ambient, Lambert diffuse from the normal map or the vertex normals,
+//specular with the exponent taken from the specular map
+pub struct DefaultShader{
+    shading:Shading,
+}
+
+impl DefaultShader{
+    pub fn new(shading:Shading)->DefaultShader{
+        DefaultShader{shading}
+    }
+
+    //lighting is only done per vertex for gouraud shading without a normal map
+    fn lit_per_vertex(&self,material:&Material)->bool{
+        matches!(self.shading,Shading::Gouraud) && material.norm_map.is_none()
+    }
+}
+
+const UV:usize=0;
+const NORMAL:usize=2;
+const POSITION:usize=5;
+//last so they can be left out when the fragments are lit
+const DIFFUSE:usize=8;
+const SPECULAR:usize=11;
+
+impl Shader for DefaultShader{
+    fn vertex(&self,uniforms:&Uniforms,vertex:&Vertex)->(Vec4,Varyings){
+        let world=uniforms.model.transform_point(&vertex.position);
+        let normal=uniforms.normal_matrix.transform_direction(&vertex.normal).normalize();
+        let mut varyings=Varyings::new();
+        varyings.push(vertex.text_coord.x).push(vertex.text_coord.y)
+            .push_vector(&normal)
+            .push_vector(&world);
+        if self.lit_per_vertex(uniforms.material){
+            let (diffuse,specular)=shade(uniforms,&world,&normal,uniforms.material.shininess);
+            varyings.push_vector(&diffuse).push_vector(&specular);
+        }
+        (*uniforms.mvp*Vec4::point(&vertex.position),varyings)
+    }
+
+    fn fragment(&self,uniforms:&Uniforms,fragment:&Fragment)->Option<TGAColor>{
+        let material=uniforms.material;
+        let varyings=&fragment.varyings;
+        let uv=(varyings.get(UV),varyings.get(UV+1));
+        //only trilinear filtering needs the footprint of the pixel
+        let (duv_dx,duv_dy)=match material.sampler.filter {
+            Filter::Trilinear=>{
+                let (dx,dy)=(fragment.dx(),fragment.dy());
+                ((dx.get(UV),dx.get(UV+1)),(dy.get(UV),dy.get(UV+1)))
+            },
+            _=>((0.,0.),(0.,0.)),
+        };
+        let sample=|texture:&Texture| texture.sample_grad(&material.sampler,uv,duv_dx,duv_dy);
+
+        let mut pixel= match &material.text_map {
+            Some(val)=>TGAColor::from_rgba(&sample(val)),
+            None=> TGAColor::new(255,255,255,255),
+        };
+        pixel.tint(&material.diffuse,material.dissolve);
+
+        let normal=match (&material.norm_map,self.shading) {
+            _ if self.lit_per_vertex(material)=>None,
+            (Some(val),_)=>{
+                //TGAColor::to_vector decodes z inverted, the map holds object space normals
+                let decoded=TGAColor::from_rgba(&sample(val)).to_vector();
+                let normal=Vector::new(decoded.x,decoded.y,-decoded.z);
+                Some(uniforms.normal_matrix.transform_direction(&normal).normalize())
+            },
+            (None,_)=>Some(varyings.vector(NORMAL).normalize()),
+        };
+        let (diffuse,specular)=match normal {
+            Some(normal)=>{
+                let shininess=match &material.sp_map {
+                    Some(val)=>sample(val)[0]*255.,
+                    None=>material.shininess,
+                };
+                shade(uniforms,&varyings.vector(POSITION),&normal,shininess)
+            },
+            None=>(varyings.vector(DIFFUSE),varyings.vector(SPECULAR)),
+        };
+
+        let base=pixel.to_rgb();
+        let (reflectance,light)=(material.ambient,uniforms.ambient);
+        let ambient=Vector::new(reflectance.x*light.x,reflectance.y*light.y,reflectance.z*light.z);
+        let spec_color=material.specular;
+        let rgb=Vector::new(base.x*(ambient.x+diffuse.x)+spec_color.x*specular.x,
+                            base.y*(ambient.y+diffuse.y)+spec_color.y*specular.y,
+                            base.z*(ambient.z+diffuse.z)+spec_color.z*specular.z);
+        Some(pixel.with_rgb(&rgb))
+    }
+}
+
+//diffuse and specular light summed over every scene light
+fn shade(uniforms:&Uniforms,position:&Vector<f32>,normal:&Vector<f32>,shininess:f32)->(Vector<f32>,Vector<f32>){
+    let view=(uniforms.eye-*position).normalize();
+    let (mut diffuse,mut specular)=(Vector::new(0.,0.,0.),Vector::new(0.,0.,0.));
+    for (index,light) in uniforms.lights.iter().enumerate(){
+        let (to_light,mut radiance)=light.illuminate(position);
+        if let Some(Some(shadow))=uniforms.shadows.get(index){
+            radiance=radiance*shadow.visibility(position);
+        }
+        let (diff,spec)=blinn_phong(normal,&to_light,&view,shininess);
+        diffuse=diffuse+radiance*diff;
+        specular=specular+radiance*spec;
+    }
+    (diffuse,specular)
+}
+
+//diffuse and specular terms for unit vectors towards the light and the eye,
+//a non-positive exponent turns the highlight off
+pub fn blinn_phong(normal:&Vector<f32>,light:&Vector<f32>,view:&Vector<f32>,shininess:f32)->(f32,f32){
+    let diffuse=normal.scalar_prod(light).max(0.);
+    if diffuse<=0. || shininess<=0.{ return (diffuse,0.) }
+    let half=(*light+*view).normalize();
+    (diffuse,normal.scalar_prod(&half).max(0.).powf(shininess))
+}
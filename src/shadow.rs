@@ -0,0 +1,111 @@
+use crate::dimensional::Vector;
+use crate::matrix::Matrix4;
+use crate::matrix::Vec4;
+use crate::light::Light;
+use crate::colors::TGAColor;
+use crate::shader::Shader;
+use crate::shader::Uniforms;
+use crate::shader::Varyings;
+use crate::shader::Fragment;
+use crate::shader::Vertex;
+
+#[derive(Copy,Clone,Debug)]
+pub struct ShadowSettings{
+    //width and height of every shadow map in texels
+    pub size:usize,
+    //depth offset in [0,1] depth units, hides self-shadowing acne
+    pub bias:f32,
+    //percentage closer filtering radius, 0 is a single sample
+    pub pcf:usize,
+}
+
+impl Default for ShadowSettings{
+    fn default()->Self{
+        ShadowSettings{size:1024,bias:0.005,pcf:1}
+    }
+}
+
+//depth of the nearest surface seen from a light, greater is closer like the image z-buffer
+pub struct ShadowMap{
+    size:usize,
+    bias:f32,
+    pcf:usize,
+    matrix:Matrix4,
+    depth:Vec<f32>,
+}
+
+impl ShadowMap{
+    //matrix goes from world space to the light's clip space, depth is the z-buffer of the
+    //depth pass in plane coordinates
+    pub fn new(settings:&ShadowSettings,matrix:Matrix4,depth:Vec<f32>)->ShadowMap{
+        let size=settings.size as f32;
+        let depth=depth.into_iter().map(|val| val/size).collect();
+        ShadowMap{size:settings.size,bias:settings.bias,pcf:settings.pcf,matrix,depth}
+    }
+
+    //fraction of the filter kernel that sees the light, 1 is fully lit
+    pub fn visibility(&self,world:&Vector<f32>)->f32{
+        let clip=self.matrix*Vec4::point(world);
+        if clip.w<=0.{ return 1. }
+        let point=clip.project().to_plane(self.size,self.size);
+        let depth=point.z/self.size as f32+self.bias;
+
+        let pcf=self.pcf as isize;
+        let (mut lit,mut total)=(0,0);
+        for dy in -pcf..=pcf{
+            for dx in -pcf..=pcf{
+                total+=1;
+                let (x,y)=(point.x as isize+dx,point.y as isize+dy);
+                if x<0 || y<0 || x>=self.size as isize || y>=self.size as isize{
+                    lit+=1;
+                    continue
+                }
+                if depth>=self.depth[y as usize*self.size+x as usize]{
+                    lit+=1;
+                }
+            }
+        }
+        lit as f32/total as f32
+    }
+}
+
+//projection*view of a light framing the bounding sphere of the scene
+pub fn light_matrix(light:&Light,center:&Vector<f32>,radius:f32)->Matrix4{
+    let radius=radius.max(f32::EPSILON);
+    //look_at turns away from an up along the view direction
+    let up=Vector::new(0.,1.,0.);
+    match light {
+        Light::Directional{direction,..}=>{
+            let eye=*center-*direction*(2.*radius);
+            Matrix4::orthographic(-radius,radius,-radius,radius,radius,3.*radius)
+                *Matrix4::look_at(&eye,center,&up)
+        },
+        Light::Point{position,..}=>{
+            let dir=*center-*position;
+            let distance=dir.length();
+            //inside the bounds nothing frames the whole scene, take the widest sane cone
+            let fov=if distance>radius{2.*(radius/distance).asin()}else{170f32.to_radians()};
+            Matrix4::perspective(fov,1.,(distance-radius).max(radius*0.01),distance+radius)
+                *Matrix4::look_at(position,center,&up)
+        },
+        Light::Spot{position,direction,outer,..}=>{
+            let distance=(*center-*position).length();
+            let fov=(2.*outer).min(170.).to_radians();
+            Matrix4::perspective(fov,1.,(distance-radius).max(radius*0.01),distance+radius)
+                *Matrix4::look_at(position,&(*position+*direction),&up)
+        },
+    }
+}
+
+//writes depth only, color is thrown away
+pub struct DepthShader;
+
+impl Shader for DepthShader{
+    fn vertex(&self,uniforms:&Uniforms,vertex:&Vertex)->(Vec4,Varyings){
+        (*uniforms.mvp*Vec4::point(&vertex.position),Varyings::new())
+    }
+
+    fn fragment(&self,_uniforms:&Uniforms,_fragment:&Fragment)->Option<TGAColor>{
+        Some(TGAColor::new(0,0,0,255))
+    }
+}
@@ -0,0 +1,96 @@
+use crate::camera::Camera;
+use crate::plane::TGAImage;
+use crate::dimensional::Vector;
+use std::f32;
+
+//golden angle in radians, spreads the samples of a Vogel disk evenly
+const GOLDEN_ANGLE:f32=2.399_963;
+
+#[derive(Copy,Clone,Debug)]
+pub struct SsaoSettings{
+    //world space distance searched for occluders
+    pub radius:f32,
+    pub samples:usize,
+    //0 leaves the image untouched, 1 blackens fully occluded pixels
+    pub strength:f32,
+}
+
+impl Default for SsaoSettings{
+    fn default()->Self{
+        SsaoSettings{radius:0.2,samples:16,strength:1.}
+    }
+}
+
+//ambient visibility of every pixel in [0,1], 1 is unoccluded.
+//depth is the linear view distance per pixel, infinite where nothing was drawn
+pub fn ambient_occlusion(depth:&[f32],height:usize,width:usize,camera:&Camera,settings:&SsaoSettings)->Vec<f32>{
+    //depth of a plane is affine in screen space only after taking the reciprocal
+    let inv=depth.iter().map(|val| 1./val).collect::<Vec<f32>>();
+    let at=|x:isize,y:isize|->Option<usize>{
+        if x<0 || y<0 || x>=width as isize || y>=height as isize{ return None }
+        let index=y as usize*width+x as usize;
+        if depth[index].is_finite(){ Some(index) }else{ None }
+    };
+    //one sided difference with the smaller slope, so silhouettes do not bend the plane
+    let slope=|index:usize,prev:Option<usize>,next:Option<usize>|->f32{
+        let back=prev.map(|prev| inv[index]-inv[prev]);
+        let front=next.map(|next| inv[next]-inv[index]);
+        match (back,front) {
+            (Some(back),Some(front))=>if back.abs()<front.abs(){back}else{front},
+            (Some(val),None) | (None,Some(val))=>val,
+            (None,None)=>0.,
+        }
+    };
+
+    let samples=settings.samples.max(1);
+    let mut res=vec![1.;height*width];
+    for y in 0..height as isize{
+        for x in 0..width as isize{
+            let index=match at(x,y) {
+                Some(index)=>index,
+                None=>continue,
+            };
+            let center=depth[index];
+            let (dx,dy)=(slope(index,at(x-1,y),at(x+1,y)),slope(index,at(x,y-1),at(x,y+1)));
+            let pixels=settings.radius*camera.pixels_per_unit(center,height);
+            //rotating the pattern per pixel trades banding for noise
+            let rotation=hash(x as usize,y as usize)*2.*f32::consts::PI;
+
+            let mut occlusion=0.;
+            for i in 0..samples{
+                let distance=pixels*((i as f32+0.5)/samples as f32).sqrt();
+                let angle=i as f32*GOLDEN_ANGLE+rotation;
+                let (ox,oy)=((distance*angle.cos()).round(),(distance*angle.sin()).round());
+                let sample=match at(x+ox as isize,y+oy as isize) {
+                    Some(sample)=>sample,
+                    None=>continue,
+                };
+                let expected=1./(inv[index]+dx*ox+dy*oy);
+                let diff=expected-depth[sample];
+                //small steps are facets of the surface itself, far ones are unrelated foreground
+                let bias=settings.radius*0.1;
+                if diff>bias && diff<settings.radius*4.{
+                    occlusion+=((diff-bias)/settings.radius).min(1.);
+                }
+            }
+            res[index]=(1.-settings.strength*occlusion/samples as f32).clamp(0.,1.);
+        }
+    }
+    res
+}
+
+//greyscale picture of the occlusion, white is unoccluded
+pub fn occlusion_image(occlusion:&[f32],height:usize,width:usize)->TGAImage{
+    let mut image=TGAImage::new(height,width);
+    image.map_pixels(|index,pixel| pixel.with_rgb(&Vector::new(occlusion[index],occlusion[index],occlusion[index])));
+    image
+}
+
+//cheap per pixel pseudo random value in [0,1)
+fn hash(x:usize,y:usize)->f32{
+    let mut val=(x as u32).wrapping_mul(73_856_093)^(y as u32).wrapping_mul(19_349_663);
+    val^=val>>13;
+    val=val.wrapping_mul(0x5bd1_e995);
+    val^=val>>15;
+    (val&0xffff) as f32/65536.
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+use std::ops::AddAssign;
+use std::time::Duration;
+
+//what one Scene::draw cost, the counts only cover the camera pass
+#[derive(Clone,Debug,Default)]
+pub struct RenderStats{
+    //threads the frame was spread over
+    pub threads:usize,
+    //wall time of the whole frame
+    pub frame:Duration,
+    //wall time of every stage, post is the ambient occlusion
+    pub shadows:Duration,
+    pub geometry:Duration,
+    pub raster:Duration,
+    pub post:Duration,
+    //busy time of every vertex processing job
+    pub geometry_jobs:Vec<Duration>,
+    pub triangles:TriangleStats,
+    pub fragments:FragmentStats,
+}
+
+//clipping can split a mesh triangle into several screen triangles, so backface_culled
+//and rasterized need not add up to what survived the frustum
+#[derive(Copy,Clone,Debug,Default)]
+pub struct TriangleStats{
+    //mesh triangles sent through the vertex stage
+    pub submitted:usize,
+    //mesh triangles entirely outside the view volume
+    pub frustum_culled:usize,
+    //screen triangles facing away from the camera
+    pub backface_culled:usize,
+    //screen triangles handed to the rasterizer
+    pub rasterized:usize,
+}
+
+#[derive(Copy,Clone,Debug,Default)]
+pub struct FragmentStats{
+    //covered pixels the fragment shader ran for
+    pub shaded:usize,
+    //covered pixels hidden behind what was already drawn
+    pub depth_rejected:usize,
+}
+
+impl AddAssign for TriangleStats{
+    fn add_assign(&mut self,rhs:TriangleStats){
+        self.submitted+=rhs.submitted;
+        self.frustum_culled+=rhs.frustum_culled;
+        self.backface_culled+=rhs.backface_culled;
+        self.rasterized+=rhs.rasterized;
+    }
+}
+
+impl AddAssign for FragmentStats{
+    fn add_assign(&mut self,rhs:FragmentStats){
+        self.shaded+=rhs.shaded;
+        self.depth_rejected+=rhs.depth_rejected;
+    }
+}
+
+//one line summary, short enough for a window title
+impl fmt::Display for RenderStats{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ms=|time:Duration| time.as_secs_f64()*1000.;
+        let (triangles,fragments)=(&self.triangles,&self.fragments);
+        write!(f,"{:.1} ms on {} threads (shadows {:.1}, geometry {:.1}, raster {:.1}, post {:.1}), "
+               ,ms(self.frame),self.threads,ms(self.shadows),ms(self.geometry),ms(self.raster),ms(self.post))?;
+        write!(f,"triangles {} submitted {} frustum culled {} backface culled {} rasterized, "
+               ,triangles.submitted,triangles.frustum_culled,triangles.backface_culled,triangles.rasterized)?;
+        write!(f,"fragments {} shaded {} depth rejected",fragments.shaded,fragments.depth_rejected)
+    }
+}
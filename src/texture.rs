@@ -1,27 +1,449 @@
 use crate::colors::Colors;
 use crate::colors::TGAColor;
+use std::error;
+use std::fmt;
+
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Filter{
+    Nearest,
+    Bilinear,
+    //bilinear on the two closest mipmap levels, blended by the level of detail
+    Trilinear,
+}
+
+//how coordinates outside [0,1] are folded back onto the texture
+#[derive(Copy,Clone,Debug,PartialEq)]
+pub enum Wrap{
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Copy,Clone,Debug)]
+pub struct Sampler{
+    pub filter:Filter,
+    pub wrap:Wrap,
+}
+
+impl Sampler{
+    pub fn new(filter:Filter,wrap:Wrap)->Sampler{
+        Sampler{filter,wrap}
+    }
+    pub fn set_filter(mut self,filter:Filter)->Self{
+        self.filter=filter;
+        self
+    }
+    pub fn set_wrap(mut self,wrap:Wrap)->Self{
+        self.wrap=wrap;
+        self
+    }
+}
+
+impl Default for Sampler{
+    fn default()->Self{
+        Sampler::new(Filter::Trilinear,Wrap::Repeat)
+    }
+}
+
+#[derive(Debug)]
+pub enum TextureError{
+    //the pixel data does not hold width*height texels, counted in bytes or texels
+    Size{expected:usize,found:usize},
+    //an accessor was used on a texture stored in another format
+    Format{expected:Colors,found:Colors},
+    OutOfBounds{x:usize,y:usize,width:usize,height:usize},
+    //a texture without texels has nothing to sample
+    Empty{width:usize,height:usize},
+}
+
+impl fmt::Display for TextureError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Size{expected,found}=>write!(f,"expected {} values, found {}",expected,found),
+            TextureError::Format{expected,found}=>write!(f,"texture is {:?}, not {:?}",found,expected),
+            TextureError::OutOfBounds{x,y,width,height}=>
+                write!(f,"texel ({}, {}) is outside the {}x{} texture",x,y,width,height),
+            TextureError::Empty{width,height}=>write!(f,"a {}x{} texture has no texels",width,height),
+        }
+    }
+}
+
+impl error::Error for TextureError{}
+
+//row-major texels, one variant per storage format
+#[derive(Clone)]
+pub enum Pixels{
+    RGBA(Vec<[u8;4]>),
+    RGB(Vec<[u8;3]>),
+    Gray(Vec<u8>),
+    RGBAFloat(Vec<[f32;4]>),
+}
+
+impl Pixels{
+    //groups tightly packed bytes into texels, floats are little endian,
+    //trailing bytes that do not make a whole texel are dropped
+    pub fn from_bytes(arr:Vec<u8>,color_type:Colors)->Pixels{
+        match color_type {
+            Colors::RGBA=>Pixels::RGBA(arr.chunks_exact(4).map(|c| [c[0],c[1],c[2],c[3]]).collect()),
+            Colors::RGB=>Pixels::RGB(arr.chunks_exact(3).map(|c| [c[0],c[1],c[2]]).collect()),
+            Colors::Gray=>Pixels::Gray(arr),
+            Colors::RGBAFloat=>Pixels::RGBAFloat(arr.chunks_exact(16).map(|c|{
+                let channel=|i:usize| f32::from_le_bytes([c[i*4],c[i*4+1],c[i*4+2],c[i*4+3]]);
+                [channel(0),channel(1),channel(2),channel(3)]
+            }).collect()),
+        }
+    }
+
+    pub fn color_type(&self)->Colors{
+        match self {
+            Pixels::RGBA(_)=>Colors::RGBA,
+            Pixels::RGB(_)=>Colors::RGB,
+            Pixels::Gray(_)=>Colors::Gray,
+            Pixels::RGBAFloat(_)=>Colors::RGBAFloat,
+        }
+    }
+
+    pub fn len(&self)->usize{
+        match self {
+            Pixels::RGBA(val)=>val.len(),
+            Pixels::RGB(val)=>val.len(),
+            Pixels::Gray(val)=>val.len(),
+            Pixels::RGBAFloat(val)=>val.len(),
+        }
+    }
+    pub fn is_empty(&self)->bool{
+        self.len()==0
+    }
+
+    //any format as normalized rgba, grey reads back as opaque grey
+    fn texel(&self,index:usize)->[f32;4]{
+        let norm=|val:u8| val as f32/255.;
+        match self {
+            Pixels::RGBA(val)=>{
+                let [r,g,b,a]=val[index];
+                [norm(r),norm(g),norm(b),norm(a)]
+            },
+            Pixels::RGB(val)=>{
+                let [r,g,b]=val[index];
+                [norm(r),norm(g),norm(b),1.]
+            },
+            Pixels::Gray(val)=>{
+                let val=norm(val[index]);
+                [val,val,val,1.]
+            },
+            Pixels::RGBAFloat(val)=>val[index],
+        }
+    }
+
+    //box filtered to half the size
+    fn halve(&self,height:usize,width:usize)->Pixels{
+        match self {
+            Pixels::RGBA(val)=>Pixels::RGBA(halve(val,height,width,|t| {
+                let mut res=[0;4];
+                for (i,channel) in res.iter_mut().enumerate(){
+                    *channel=average([t[0][i],t[1][i],t[2][i],t[3][i]]);
+                }
+                res
+            })),
+            Pixels::RGB(val)=>Pixels::RGB(halve(val,height,width,|t| {
+                let mut res=[0;3];
+                for (i,channel) in res.iter_mut().enumerate(){
+                    *channel=average([t[0][i],t[1][i],t[2][i],t[3][i]]);
+                }
+                res
+            })),
+            Pixels::Gray(val)=>Pixels::Gray(halve(val,height,width,average)),
+            Pixels::RGBAFloat(val)=>Pixels::RGBAFloat(halve(val,height,width,|t| {
+                let mut res=[0.;4];
+                for (i,channel) in res.iter_mut().enumerate(){
+                    *channel=(t[0][i]+t[1][i]+t[2][i]+t[3][i])/4.;
+                }
+                res
+            })),
+        }
+    }
+}
+
+//one level of the mipmap chain
+struct Level{
+    height:usize,
+    width:usize,
+    pixels:Pixels,
+}
 
 pub struct Texture{
     pub height:usize,
     pub width:usize,
-    arr:Vec<u8>,
-    color_type:Colors,
+    pixels:Pixels,
+    //halved levels down to 1x1, the full sized image is pixels
+    mipmaps:Vec<Level>,
 }
 
 impl Texture{
-    pub fn new(height:usize,width:usize,arr:Vec<u8>,color_type:Colors)->Texture{
-        Texture{height,width,arr,color_type }
-    }
-    pub fn get_pixel(&self,x:usize,y:usize)->TGAColor{
-        let index=y*self.width+x;
-        let vec=self.arr.iter()
-            .skip(index*4)
-            .take(4)
-            .collect::<Vec<&u8>>();
-        TGAColor::new(*vec[0],*vec[1],*vec[2],*vec[3])
-    }
-    pub fn get_pixel_grey(&self,x:usize,y:usize)->f32 {
-        let index=y*self.width+x;
-        self.arr[index] as f32/255.
-    }
-}
\ No newline at end of file
+    //arr holds tightly packed texels, see Pixels::from_bytes
+    pub fn new(height:usize,width:usize,arr:Vec<u8>,color_type:Colors)->Result<Texture,TextureError>{
+        let expected=height*width*color_type.bytes();
+        if arr.len()!=expected{
+            return Err(TextureError::Size{expected,found:arr.len()})
+        }
+        Texture::from_pixels(height,width,Pixels::from_bytes(arr,color_type))
+    }
+    pub fn from_pixels(height:usize,width:usize,pixels:Pixels)->Result<Texture,TextureError>{
+        if height==0 || width==0{
+            return Err(TextureError::Empty{width,height})
+        }
+        if pixels.len()!=height*width{
+            return Err(TextureError::Size{expected:height*width,found:pixels.len()})
+        }
+        let mipmaps=build_mipmaps(height,width,&pixels);
+        Ok(Texture{height,width,pixels,mipmaps})
+    }
+
+    pub fn color_type(&self)->Colors{
+        self.pixels.color_type()
+    }
+    pub fn pixels(&self)->&Pixels{
+        &self.pixels
+    }
+
+    //8 bit color formats only, rgb reads back opaque
+    pub fn get_pixel(&self,x:usize,y:usize)->Result<TGAColor,TextureError>{
+        let index=self.index(x,y)?;
+        match &self.pixels {
+            Pixels::RGBA(val)=>{
+                let [r,g,b,a]=val[index];
+                Ok(TGAColor::new(r,g,b,a))
+            },
+            Pixels::RGB(val)=>{
+                let [r,g,b]=val[index];
+                Ok(TGAColor::new(r,g,b,255))
+            },
+            _=>Err(TextureError::Format{expected:Colors::RGBA,found:self.color_type()}),
+        }
+    }
+    //Gray only, in [0,1]
+    pub fn get_pixel_grey(&self,x:usize,y:usize)->Result<f32,TextureError>{
+        let index=self.index(x,y)?;
+        match &self.pixels {
+            Pixels::Gray(val)=>Ok(val[index] as f32/255.),
+            _=>Err(TextureError::Format{expected:Colors::Gray,found:self.color_type()}),
+        }
+    }
+    //RGBAFloat only
+    pub fn get_pixel_float(&self,x:usize,y:usize)->Result<[f32;4],TextureError>{
+        let index=self.index(x,y)?;
+        match &self.pixels {
+            Pixels::RGBAFloat(val)=>Ok(val[index]),
+            _=>Err(TextureError::Format{expected:Colors::RGBAFloat,found:self.color_type()}),
+        }
+    }
+
+    fn index(&self,x:usize,y:usize)->Result<usize,TextureError>{
+        if x>=self.width || y>=self.height{
+            return Err(TextureError::OutOfBounds{x,y,width:self.width,height:self.height})
+        }
+        Ok(y*self.width+x)
+    }
+
+    //number of mipmap levels including the full sized one
+    pub fn levels(&self)->usize{
+        self.mipmaps.len()+1
+    }
+
+    //normalized rgba at normalized uv, trilinear filtering reads the full sized level
+    pub fn sample(&self,sampler:&Sampler,uv:(f32,f32))->[f32;4]{
+        self.sample_lod(sampler,uv,0.)
+    }
+
+    //duv_dx and duv_dy are the uv steps to the next pixel on screen, they pick the mipmap level
+    pub fn sample_grad(&self,sampler:&Sampler,uv:(f32,f32),duv_dx:(f32,f32),duv_dy:(f32,f32))->[f32;4]{
+        let (width,height)=(self.width as f32,self.height as f32);
+        let len=|d:(f32,f32)| ((d.0*width).powi(2)+(d.1*height).powi(2)).sqrt();
+        let footprint=len(duv_dx).max(len(duv_dy));
+        let lod=if footprint>1.{footprint.log2()}else{0.};
+        self.sample_lod(sampler,uv,lod)
+    }
+
+    pub fn sample_lod(&self,sampler:&Sampler,uv:(f32,f32),lod:f32)->[f32;4]{
+        match sampler.filter {
+            Filter::Nearest=>self.nearest(0,sampler.wrap,uv),
+            Filter::Bilinear=>self.bilinear(0,sampler.wrap,uv),
+            Filter::Trilinear=>{
+                let lod=lod.max(0.).min(self.mipmaps.len() as f32);
+                let (level,t)=(lod.floor() as usize,lod.fract());
+                let low=self.bilinear(level,sampler.wrap,uv);
+                if t==0. || level+1>=self.levels(){ return low }
+                let high=self.bilinear(level+1,sampler.wrap,uv);
+                let mut res=[0.;4];
+                for i in 0..4{
+                    res[i]=low[i]+(high[i]-low[i])*t;
+                }
+                res
+            },
+        }
+    }
+
+    fn level(&self,level:usize)->(usize,usize,&Pixels){
+        match level {
+            0=>(self.height,self.width,&self.pixels),
+            _=>{
+                let level=&self.mipmaps[level-1];
+                (level.height,level.width,&level.pixels)
+            },
+        }
+    }
+
+    fn fetch(&self,level:usize,x:usize,y:usize)->[f32;4]{
+        let (_,width,pixels)=self.level(level);
+        pixels.texel(y*width+x)
+    }
+
+    fn nearest(&self,level:usize,wrap:Wrap,uv:(f32,f32))->[f32;4]{
+        let (height,width,_)=self.level(level);
+        let x=wrap_coord(wrap,(uv.0*width as f32).floor() as isize,width);
+        let y=wrap_coord(wrap,(uv.1*height as f32).floor() as isize,height);
+        self.fetch(level,x,y)
+    }
+
+    fn bilinear(&self,level:usize,wrap:Wrap,uv:(f32,f32))->[f32;4]{
+        let (height,width,_)=self.level(level);
+        //texel centers sit at half integers
+        let (x,y)=(uv.0*width as f32-0.5,uv.1*height as f32-0.5);
+        let (x0,y0)=(x.floor(),y.floor());
+        let (tx,ty)=(x-x0,y-y0);
+        let (x0,y0)=(x0 as isize,y0 as isize);
+        let (left,right)=(wrap_coord(wrap,x0,width),wrap_coord(wrap,x0+1,width));
+        let (bottom,top)=(wrap_coord(wrap,y0,height),wrap_coord(wrap,y0+1,height));
+        let (a,b,c,d)=(self.fetch(level,left,bottom),self.fetch(level,right,bottom)
+                       ,self.fetch(level,left,top),self.fetch(level,right,top));
+        let mut res=[0.;4];
+        for i in 0..4{
+            let near=a[i]+(b[i]-a[i])*tx;
+            let far=c[i]+(d[i]-c[i])*tx;
+            res[i]=near+(far-near)*ty;
+        }
+        res
+    }
+}
+
+fn wrap_coord(wrap:Wrap,coord:isize,size:usize)->usize{
+    let size=size as isize;
+    let res=match wrap {
+        Wrap::Repeat=>coord.rem_euclid(size),
+        Wrap::Clamp=>coord.max(0).min(size-1),
+        Wrap::Mirror=>{
+            let period=coord.rem_euclid(2*size);
+            if period<size{period}else{2*size-1-period}
+        },
+    };
+    res as usize
+}
+
+//halvings down to 1x1
+fn build_mipmaps(height:usize,width:usize,pixels:&Pixels)->Vec<Level>{
+    let mut levels:Vec<Level>=Vec::new();
+    let (mut height,mut width)=(height,width);
+    while height>1 || width>1{
+        let next=match levels.last() {
+            Some(level)=>level.pixels.halve(height,width),
+            None=>pixels.halve(height,width),
+        };
+        height=(height/2).max(1);
+        width=(width/2).max(1);
+        levels.push(Level{height,width,pixels:next});
+    }
+    levels
+}
+
+//every texel of the result averages a 2x2 block, odd edges reuse the last row or column
+fn halve<T:Copy,F:Fn([T;4])->T>(src:&[T],height:usize,width:usize,average:F)->Vec<T>{
+    let (res_height,res_width)=((height/2).max(1),(width/2).max(1));
+    let mut res=Vec::with_capacity(res_height*res_width);
+    for y in 0..res_height{
+        for x in 0..res_width{
+            let (ys,xs)=((y*2).min(height-1),(x*2).min(width-1));
+            let (ye,xe)=((y*2+1).min(height-1),(x*2+1).min(width-1));
+            res.push(average([src[ys*width+xs],src[ys*width+xe],src[ye*width+xs],src[ye*width+xe]]));
+        }
+    }
+    res
+}
+
+fn average(vals:[u8;4])->u8{
+    ((vals.iter().map(|&val| val as u32).sum::<u32>()+2)/4) as u8
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn zero_size_is_rejected(){
+        for (height,width) in [(0,0),(0,3),(3,0)]{
+            assert!(matches!(Texture::new(height,width,Vec::new(),Colors::Gray),Err(TextureError::Empty{..})));
+            assert!(matches!(Texture::from_pixels(height,width,Pixels::RGBA(Vec::new())),Err(TextureError::Empty{..})));
+        }
+        assert!(Texture::new(1,1,vec![7],Colors::Gray).is_ok());
+    }
+
+    //4x2 rgba with a different value in every channel of every texel
+    fn texture()->Texture{
+        let arr=(0..32).map(|val| val*8).collect();
+        Texture::new(2,4,arr,Colors::RGBA).unwrap()
+    }
+
+    #[test]
+    fn wrap_modes_fold_coordinates_back(){
+        let folded=|wrap,coords:&[isize]| coords.iter().map(|&coord| wrap_coord(wrap,coord,4)).collect::<Vec<_>>();
+        let coords=[-5,-2,-1,0,3,4,5,8,9];
+        assert_eq!(folded(Wrap::Repeat,&coords),[3,2,3,0,3,0,1,0,1]);
+        assert_eq!(folded(Wrap::Clamp,&coords),[0,0,0,0,3,3,3,3,3]);
+        assert_eq!(folded(Wrap::Mirror,&coords),[3,1,0,0,3,3,2,0,1]);
+        //the same through uv, a quarter texel past either edge
+        let texture=Texture::new(1,4,vec![0,85,170,255],Colors::Gray).unwrap();
+        let sample=|wrap,u| texture.sample(&Sampler::new(Filter::Nearest,wrap),(u,0.5))[0];
+        assert_eq!([sample(Wrap::Repeat,-0.0625),sample(Wrap::Repeat,1.0625)],[1.,0.]);
+        assert_eq!([sample(Wrap::Clamp,-0.0625),sample(Wrap::Clamp,1.0625)],[0.,1.]);
+        assert_eq!([sample(Wrap::Mirror,-0.0625),sample(Wrap::Mirror,1.0625)],[0.,1.]);
+    }
+
+    #[test]
+    fn bilinear_at_texel_centers_is_exact(){
+        let texture=texture();
+        for wrap in [Wrap::Repeat,Wrap::Clamp,Wrap::Mirror]{
+            let sampler=Sampler::new(Filter::Bilinear,wrap);
+            for y in 0..2{
+                for x in 0..4{
+                    let uv=((x as f32+0.5)/4.,(y as f32+0.5)/2.);
+                    assert_eq!(texture.sample(&sampler,uv),texture.fetch(0,x,y),"{:?} at {},{}",wrap,x,y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mipmaps_of_a_non_square_texture(){
+        //rows 0,10,..,70 and 100,110,..,170
+        let arr=(0..16).map(|val| (val/8*100+val%8*10) as u8).collect();
+        let texture=Texture::new(2,8,arr,Colors::Gray).unwrap();
+        assert_eq!(texture.levels(),4);
+        let levels:Vec<(usize,usize,Vec<u8>)>=texture.mipmaps.iter().map(|level| match &level.pixels {
+            Pixels::Gray(val)=>(level.width,level.height,val.clone()),
+            _=>panic!("mipmaps keep the format"),
+        }).collect();
+        assert_eq!(levels,[(4,1,vec![55,75,95,115]),(2,1,vec![65,105]),(1,1,vec![85])]);
+    }
+
+    #[test]
+    fn trilinear_at_lod_0_is_bilinear(){
+        let texture=texture();
+        let (trilinear,bilinear)=(Sampler::new(Filter::Trilinear,Wrap::Repeat),Sampler::new(Filter::Bilinear,Wrap::Repeat));
+        for &uv in [(0.,0.),(0.3,0.7),(0.9,0.1),(-0.4,1.6)].iter(){
+            let expected=texture.sample(&bilinear,uv);
+            assert_eq!(texture.sample_lod(&trilinear,uv,0.),expected);
+            //lods below 0 magnify and stay on the full sized level too
+            assert_eq!(texture.sample_lod(&trilinear,uv,-2.),expected);
+            assert_eq!(texture.sample_grad(&trilinear,uv,(0.1,0.),(0.,0.2)),expected);
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use crate::colors::Colors;
+use crate::texture::Pixels;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+
+const HEADER_LEN:usize=18;
+const TRUE_COLOR:u8=2;
+const GRAY:u8=3;
+const RLE_TRUE_COLOR:u8=10;
+const RLE_GRAY:u8=11;
+//image descriptor bits
+const RIGHT_ORIGIN:u8=0x10;
+const TOP_ORIGIN:u8=0x20;
+//a packet holds at most 128 pixels
+const MAX_PACKET:usize=128;
+
+//decoded pixels in rgb order, rows run top to bottom whatever the origin of the file
+pub struct Tga{
+    pub width:usize,
+    pub height:usize,
+    //Gray, RGB or RGBA
+    pub color_type:Colors,
+    pub data:Vec<u8>,
+}
+
+impl Tga{
+    //converts to any texel format, color to grey uses the luminance weights of imagefmt
+    pub fn into_pixels(self,color_type:Colors)->Pixels{
+        let channels=self.color_type.bytes();
+        if self.color_type==color_type{
+            return Pixels::from_bytes(self.data,color_type)
+        }
+        let rgba=self.data.chunks_exact(channels).map(|c| match channels {
+            1=>[c[0],c[0],c[0],255],
+            3=>[c[0],c[1],c[2],255],
+            _=>[c[0],c[1],c[2],c[3]],
+        });
+        match color_type {
+            Colors::RGBA=>Pixels::RGBA(rgba.collect()),
+            Colors::RGB=>Pixels::RGB(rgba.map(|[r,g,b,_]| [r,g,b]).collect()),
+            Colors::Gray=>Pixels::Gray(rgba
+                .map(|[r,g,b,_]| (0.21*r as f32+0.64*g as f32+0.15*b as f32) as u8)
+                .collect()),
+            Colors::RGBAFloat=>Pixels::RGBAFloat(rgba
+                .map(|c| [c[0] as f32/255.,c[1] as f32/255.,c[2] as f32/255.,c[3] as f32/255.])
+                .collect()),
+        }
+    }
+}
+
+fn invalid(message:String)->io::Error{
+    io::Error::new(io::ErrorKind::InvalidData,message)
+}
+
+//appends exactly len bytes to data
+fn read_bytes<R:Read>(reader:&mut R,data:&mut Vec<u8>,len:usize)->io::Result<()>{
+    let start=data.len();
+    reader.take(len as u64).read_to_end(data)?;
+    if data.len()-start<len{
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof,"tga pixel data is cut short"))
+    }
+    Ok(())
+}
+
+//uncompressed and run length encoded 8 bit grey, 24 and 32 bit true color
+pub fn read<R:Read>(mut reader:R)->io::Result<Tga>{
+    let mut header=[0u8;HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    let (id_len,map_type,image_type)=(header[0] as usize,header[1],header[2]);
+    let map_len=u16::from_le_bytes([header[5],header[6]]) as usize;
+    let map_entry=(header[7] as usize).div_ceil(8);
+    let width=u16::from_le_bytes([header[12],header[13]]) as usize;
+    let height=u16::from_le_bytes([header[14],header[15]]) as usize;
+    let (depth,descriptor)=(header[16],header[17]);
+    if width==0 || height==0{
+        return Err(invalid(format!("tga of {}x{} pixels has no rows to read",width,height)))
+    }
+
+    let (color_type,rle)=match (image_type,depth) {
+        (TRUE_COLOR,24)=>(Colors::RGB,false),
+        (TRUE_COLOR,32)=>(Colors::RGBA,false),
+        (GRAY,8)=>(Colors::Gray,false),
+        (RLE_TRUE_COLOR,24)=>(Colors::RGB,true),
+        (RLE_TRUE_COLOR,32)=>(Colors::RGBA,true),
+        (RLE_GRAY,8)=>(Colors::Gray,true),
+        _=>return Err(invalid(format!("unsupported tga image type {} with {} bits per pixel",image_type,depth))),
+    };
+    //the id and a color map unused by true color images
+    let skip=id_len+if map_type!=0{map_len*map_entry}else{0};
+    io::copy(&mut (&mut reader).take(skip as u64),&mut io::sink())?;
+
+    let bytes=color_type.bytes();
+    let size=width*height*bytes;
+    //grows as pixels arrive, so a corrupt header cannot make us allocate gigabytes up front
+    let mut data=Vec::new();
+    if rle{
+        while data.len()<size{
+            let mut packet=[0u8;1];
+            reader.read_exact(&mut packet)?;
+            let count=(packet[0] as usize&0x7f)+1;
+            let len=(count*bytes).min(size-data.len());
+            if packet[0]&0x80!=0{
+                let mut pixel=[0u8;4];
+                reader.read_exact(&mut pixel[..bytes])?;
+                data.extend(pixel[..bytes].iter().cycle().take(len));
+            }else{
+                read_bytes(&mut reader,&mut data,len)?;
+            }
+        }
+    }else{
+        read_bytes(&mut reader,&mut data,size)?;
+    }
+
+    if bytes>=3{
+        for pixel in data.chunks_exact_mut(bytes){
+            pixel.swap(0,2);
+        }
+    }
+    let row=width*bytes;
+    if descriptor&RIGHT_ORIGIN!=0{
+        for line in data.chunks_exact_mut(row){
+            let mut pixels=line.chunks_exact(bytes).rev().flatten().cloned().collect::<Vec<u8>>();
+            line.swap_with_slice(&mut pixels);
+        }
+    }
+    if descriptor&TOP_ORIGIN==0{
+        data=data.chunks_exact(row).rev().flatten().cloned().collect();
+    }
+    Ok(Tga{width,height,color_type,data})
+}
+
+//rows are taken top to bottom and written with a top left origin
+pub fn write<W:Write>(mut writer:W,tga:&Tga,rle:bool)->io::Result<()>{
+    let (image_type,descriptor)=match (&tga.color_type,rle) {
+        (Colors::Gray,false)=>(GRAY,TOP_ORIGIN),
+        (Colors::Gray,true)=>(RLE_GRAY,TOP_ORIGIN),
+        (Colors::RGB,false)=>(TRUE_COLOR,TOP_ORIGIN),
+        (Colors::RGB,true)=>(RLE_TRUE_COLOR,TOP_ORIGIN),
+        //the low bits count the alpha bits per pixel
+        (Colors::RGBA,false)=>(TRUE_COLOR,TOP_ORIGIN|8),
+        (Colors::RGBA,true)=>(RLE_TRUE_COLOR,TOP_ORIGIN|8),
+        (Colors::RGBAFloat,_)=>return Err(io::Error::new(io::ErrorKind::InvalidInput
+                                                          ,"tga stores 8 bits per channel")),
+    };
+    if tga.width>u16::MAX as usize || tga.height>u16::MAX as usize{
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,"tga is at most 65535 pixels wide and high"))
+    }
+    if tga.width==0 || tga.height==0{
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,"tga is at least 1 pixel wide and high"))
+    }
+    let bytes=tga.color_type.bytes();
+    if tga.data.len()!=tga.width*tga.height*bytes{
+        return Err(io::Error::new(io::ErrorKind::InvalidInput
+                                  ,format!("expected {} bytes, found {}",tga.width*tga.height*bytes,tga.data.len())))
+    }
+
+    let mut header=[0u8;HEADER_LEN];
+    header[2]=image_type;
+    header[12..14].copy_from_slice(&(tga.width as u16).to_le_bytes());
+    header[14..16].copy_from_slice(&(tga.height as u16).to_le_bytes());
+    header[16]=(bytes*8) as u8;
+    header[17]=descriptor;
+    writer.write_all(&header)?;
+
+    let mut data=tga.data.clone();
+    if bytes>=3{
+        for pixel in data.chunks_exact_mut(bytes){
+            pixel.swap(0,2);
+        }
+    }
+    if !rle{
+        return writer.write_all(&data)
+    }
+    //packets never cross a scanline
+    let mut out=Vec::with_capacity(data.len());
+    for line in data.chunks_exact(tga.width*bytes){
+        let pixels=line.chunks_exact(bytes).collect::<Vec<&[u8]>>();
+        let mut i=0;
+        while i<pixels.len(){
+            let mut run=1;
+            while i+run<pixels.len() && run<MAX_PACKET && pixels[i+run]==pixels[i]{
+                run+=1;
+            }
+            if run>1{
+                out.push(0x80|(run-1) as u8);
+                out.extend_from_slice(pixels[i]);
+                i+=run;
+                continue
+            }
+            //raw pixels up to the start of the next run
+            let start=i;
+            while i<pixels.len() && i-start<MAX_PACKET && (i+1>=pixels.len() || pixels[i+1]!=pixels[i]){
+                i+=1;
+            }
+            out.push((i-start-1) as u8);
+            for pixel in &pixels[start..i]{
+                out.extend_from_slice(pixel);
+            }
+        }
+    }
+    writer.write_all(&out)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn header(image_type:u8,width:u16,height:u16,depth:u8)->Vec<u8>{
+        let mut header=vec![0u8;HEADER_LEN];
+        header[2]=image_type;
+        header[12..14].copy_from_slice(&width.to_le_bytes());
+        header[14..16].copy_from_slice(&height.to_le_bytes());
+        header[16]=depth;
+        header
+    }
+
+    //runs longer than a packet, single pixels and runs at the end of a row
+    fn image(color_type:Colors,width:usize,height:usize)->Tga{
+        let bytes=color_type.bytes();
+        let data=(0..width*height*bytes).map(|i| ((i/bytes)%width/3*7+i%bytes) as u8).collect();
+        Tga{width,height,color_type,data}
+    }
+
+    fn round_trip(tga:&Tga,rle:bool)->Tga{
+        let mut file=Vec::new();
+        write(&mut file,tga,rle).unwrap();
+        read(&file[..]).unwrap()
+    }
+
+    #[test]
+    fn rle_round_trip(){
+        for color_type in [Colors::Gray,Colors::RGB,Colors::RGBA]{
+            for (width,height) in [(1,1),(5,3),(300,2)]{
+                let tga=image(color_type,width,height);
+                for rle in [false,true]{
+                    let read=round_trip(&tga,rle);
+                    assert_eq!((read.width,read.height),(width,height));
+                    assert!(read.color_type==color_type);
+                    assert_eq!(read.data,tga.data);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rle_compresses_runs(){
+        let tga=Tga{width:256,height:1,color_type:Colors::RGB,data:vec![9;256*3]};
+        let mut file=Vec::new();
+        write(&mut file,&tga,true).unwrap();
+        //two full run packets of one pixel each
+        assert_eq!(file.len(),HEADER_LEN+2*4);
+    }
+
+    #[test]
+    fn bottom_origin_rows_are_flipped(){
+        let mut file=header(GRAY,1,2,8);
+        file.extend_from_slice(&[1,2]);
+        assert_eq!(read(&file[..]).unwrap().data,vec![2,1]);
+    }
+
+    #[test]
+    fn zero_size_is_rejected(){
+        for (width,height) in [(0,1),(1,0),(0,0)]{
+            let error=read(&header(TRUE_COLOR,width,height,24)[..]).err().unwrap();
+            assert_eq!(error.kind(),io::ErrorKind::InvalidData);
+            let tga=Tga{width:width as usize,height:height as usize,color_type:Colors::RGB,data:Vec::new()};
+            let error=write(Vec::new(),&tga,false).err().unwrap();
+            assert_eq!(error.kind(),io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn short_data_is_an_error(){
+        let mut file=header(TRUE_COLOR,u16::MAX,u16::MAX,32);
+        file.extend_from_slice(&[0;10]);
+        assert_eq!(read(&file[..]).err().unwrap().kind(),io::ErrorKind::UnexpectedEof);
+        let mut file=header(RLE_TRUE_COLOR,u16::MAX,u16::MAX,32);
+        file.extend_from_slice(&[0xff,1,2,3,4]);
+        assert_eq!(read(&file[..]).err().unwrap().kind(),io::ErrorKind::UnexpectedEof);
+    }
+}